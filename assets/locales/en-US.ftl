@@ -0,0 +1,32 @@
+# Scorecard brochure, English.
+city-scorecard = City scorecard
+network-analysis = Bicycle network analysis
+infrastructure-miles = Infrastructure mileage
+overall = Overall
+score = Score
+out-of = out of
+neighborhoods = People
+neighborhoods-description = Access to where other people live
+opportunity = Opportunity
+opportunity-description = Access to jobs and education
+essential-services = Core Services
+essential-services-description = Access to basic needs
+retail = Retail
+retail-description = Access to shopping centers
+recreation = Recreation
+recreation-description = Access to parks and trails
+transit = Transit
+transit-description = Access to major transit hubs
+low-stress-network = { $unit ->
+    [km] Kilometers
+   *[other] Miles
+} of low-stress roads and paths
+low-stress-network-separated = Roads where cars and bikes are separated
+low-stress-network-low-speed = or travel speeds are low
+high-stress-network = { $unit ->
+    [km] Kilometers
+   *[other] Miles
+} of high-stress roads
+high-stress-network-description = Roads lacking adequate bike infrastructure
+unit-miles = miles
+unit-kilometers = km
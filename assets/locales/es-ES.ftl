@@ -0,0 +1,26 @@
+# Scorecard brochure, Spanish.
+city-scorecard = Ficha de la ciudad
+network-analysis = Análisis de la red ciclista
+infrastructure-miles = Kilómetros de infraestructura
+overall = General
+score = Puntuación
+out-of = de
+neighborhoods = Personas
+neighborhoods-description = Acceso a donde vive otra gente
+opportunity = Oportunidades
+opportunity-description = Acceso al empleo y la educación
+essential-services = Servicios básicos
+essential-services-description = Acceso a las necesidades básicas
+retail = Comercios
+retail-description = Acceso a los centros comerciales
+recreation = Recreación
+recreation-description = Acceso a parques y senderos
+transit = Transporte público
+transit-description = Acceso a los principales nodos de transporte
+low-stress-network = Vías y caminos de bajo estrés ({ $unit })
+low-stress-network-separated = Vías donde coches y bicicletas están separados
+low-stress-network-low-speed = o la velocidad es baja
+high-stress-network = Vías de alto estrés ({ $unit })
+high-stress-network-description = Vías sin infraestructura ciclista adecuada
+unit-miles = millas
+unit-kilometers = km
@@ -0,0 +1,26 @@
+# Scorecard brochure, French.
+city-scorecard = Bulletin de la ville
+network-analysis = Analyse du réseau cyclable
+infrastructure-miles = Kilomètres d'infrastructure
+overall = Global
+score = Score
+out-of = sur
+neighborhoods = Population
+neighborhoods-description = Accès aux lieux de vie des autres habitants
+opportunity = Opportunités
+opportunity-description = Accès à l'emploi et à l'éducation
+essential-services = Services de base
+essential-services-description = Accès aux besoins essentiels
+retail = Commerces
+retail-description = Accès aux centres commerciaux
+recreation = Loisirs
+recreation-description = Accès aux parcs et aux sentiers
+transit = Transports en commun
+transit-description = Accès aux principaux pôles de transport
+low-stress-network = Routes et chemins à faible stress ({ $unit })
+low-stress-network-separated = Routes où voitures et vélos sont séparés
+low-stress-network-low-speed = ou la vitesse est faible
+high-stress-network = Routes à stress élevé ({ $unit })
+high-stress-network-description = Routes sans aménagement cyclable adapté
+unit-miles = miles
+unit-kilometers = km
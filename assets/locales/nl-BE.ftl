@@ -0,0 +1,26 @@
+# Scorecard brochure, Dutch.
+city-scorecard = Stadsrapport
+network-analysis = Analyse van het fietsnetwerk
+infrastructure-miles = Kilometers infrastructuur
+overall = Totaal
+score = Score
+out-of = van de
+neighborhoods = Mensen
+neighborhoods-description = Toegang tot waar andere mensen wonen
+opportunity = Kansen
+opportunity-description = Toegang tot werk en onderwijs
+essential-services = Basisvoorzieningen
+essential-services-description = Toegang tot eerste levensbehoeften
+retail = Winkels
+retail-description = Toegang tot winkelcentra
+recreation = Recreatie
+recreation-description = Toegang tot parken en paden
+transit = Openbaar vervoer
+transit-description = Toegang tot grote OV-knooppunten
+low-stress-network = Wegen en paden met weinig stress ({ $unit })
+low-stress-network-separated = Wegen waar auto's en fietsen gescheiden zijn
+low-stress-network-low-speed = of de snelheid laag is
+high-stress-network = Wegen met veel stress ({ $unit })
+high-stress-network-description = Wegen zonder geschikte fietsinfrastructuur
+unit-miles = mijl
+unit-kilometers = km
@@ -1,12 +1,5 @@
-use bnacore::neon::{
-    model::{
-        Branch, CreateBranchRequest, CreateBranchResponse, Endpoint, EndpointType,
-        ListBranchResponses,
-    },
-    NEON_PROJECTS_URL,
-};
+use bnacore::neon;
 use color_eyre::Result;
-use reqwest::header::{self, HeaderValue};
 use std::env;
 
 #[tokio::main]
@@ -19,46 +12,31 @@ async fn main() -> Result<()> {
 
     // Create the Neon client.
     let neon_api_key = env::var("NEON_API_KEY")?;
-    let mut headers = header::HeaderMap::new();
-    let mut auth_value = HeaderValue::from_str(format!("Bearer {}", neon_api_key).as_ref())?;
-    auth_value.set_sensitive(true);
-    headers.insert(header::AUTHORIZATION, auth_value);
-    let neon = reqwest::Client::builder()
-        .default_headers(headers)
-        .build()?;
     let neon_project_id = env::var("NEON_PROJECT_ID")?;
-    let neon_branches_url = format!("{}/{}/branches", NEON_PROJECTS_URL, neon_project_id);
+    let neon = neon::Client::new(&neon_api_key, &neon_project_id)?;
 
     // Query neon API and check whether we can create a branch or not.
-    let branches = neon
-        .get(&neon_branches_url)
-        .send()
-        .await?
-        .error_for_status()?
-        .json::<ListBranchResponses>()
-        .await?;
+    let branches = neon.get_branches().await?;
     dbg!(&branches);
 
-    let branch_name = "usa-santa-rosa-new-mexico".to_string();
-    let create_branch_request = CreateBranchRequest {
-        endpoints: vec![Endpoint {
-            r#type: EndpointType::ReadWrite,
-            ..Default::default()
-        }],
-        branch: Branch {
-            name: Some(branch_name),
-            ..Default::default()
-        },
-    };
-    let create_branch_response = neon
-        .post(&neon_branches_url)
-        .json(&create_branch_request)
-        .send()
-        .await?
-        .error_for_status()?
-        .json::<CreateBranchResponse>()
-        .await?;
+    let branch_name = "usa-santa-rosa-new-mexico";
+    let create_branch_response = neon.create_branch(branch_name).await?;
     dbg!(&create_branch_response);
 
+    // Prepare a database owned by a dedicated role.
+    let branch_id = create_branch_response.branch.id.unwrap_or_default();
+    let role = neon.create_role(&branch_id, "bna_example").await?;
+    dbg!(&role);
+    let database = neon
+        .create_database(&branch_id, "bna_example", "bna_example")
+        .await?;
+    dbg!(&database);
+
+    // Retrieve the connection URI of the database.
+    let connection_uri = neon
+        .get_connection_uri(&branch_id, None, "bna_example", "bna_example")
+        .await?;
+    dbg!(&connection_uri);
+
     Ok(())
 }
@@ -0,0 +1,233 @@
+//! Authenticate the requests to the BNA API.
+//!
+//! The service accounts obtain their access tokens with the OAuth2 client
+//! credentials flow. The tokens are cached until shortly before they expire, so
+//! that a warm lambda does not request a new one on every invocation.
+use crate::{
+    http::{http_client, HttpClient},
+    Error,
+};
+use serde::Deserialize;
+use std::{fmt, future::Future, sync::Arc, time::Duration};
+use tokio::{sync::Mutex, time::Instant};
+use url::Url;
+
+/// Default time before the expiration of a token at which it gets refreshed.
+pub const DEFAULT_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Define the response of an OAuth2 token endpoint.
+#[derive(Clone, Deserialize)]
+pub struct AuthResponse {
+    pub access_token: String,
+    /// Lifetime of the token, in seconds.
+    pub expires_in: u64,
+    pub token_type: String,
+}
+
+impl fmt::Debug for AuthResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthResponse")
+            .field("access_token", &"redacted")
+            .field("expires_in", &self.expires_in)
+            .field("token_type", &self.token_type)
+            .finish()
+    }
+}
+
+/// Provide the access tokens authenticating the requests.
+pub trait AuthProvider {
+    /// Return a valid access token.
+    fn access_token(&self) -> impl Future<Output = Result<String, Error>> + Send;
+}
+
+/// Use the same access token for every request.
+///
+/// The token is never refreshed, which is mostly useful for tests and local
+/// runs.
+#[derive(Clone)]
+pub struct StaticToken(pub String);
+
+impl AuthProvider for StaticToken {
+    async fn access_token(&self) -> Result<String, Error> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Access token cached by [`ClientCredentials`].
+struct CachedToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+/// Obtain the access tokens with the OAuth2 client credentials flow.
+///
+/// The token is cached and shared by the clones of the provider. It is
+/// refreshed once it expires in less than the refresh margin.
+#[derive(Clone)]
+pub struct ClientCredentials {
+    client: HttpClient,
+    token_url: Url,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    refresh_margin: Duration,
+    cache: Arc<Mutex<Option<CachedToken>>>,
+}
+
+impl ClientCredentials {
+    /// Create a provider requesting its tokens from `token_url`.
+    pub fn new(token_url: Url, client_id: &str, client_secret: &str) -> Self {
+        Self {
+            client: http_client().clone(),
+            token_url,
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            scope: None,
+            refresh_margin: DEFAULT_REFRESH_MARGIN,
+            cache: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Request the tokens for a specific scope.
+    pub fn scope(mut self, scope: &str) -> Self {
+        self.scope = Some(scope.into());
+        self
+    }
+
+    /// Set the time before the expiration of a token at which it gets
+    /// refreshed.
+    pub fn refresh_margin(mut self, refresh_margin: Duration) -> Self {
+        self.refresh_margin = refresh_margin;
+        self
+    }
+
+    /// Use another HTTP client.
+    pub fn with_http_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Request a new token, bypassing the cache.
+    pub async fn request_token(&self) -> Result<AuthResponse, Error> {
+        let mut form = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope.as_str()));
+        }
+        let request = self
+            .client
+            .post(self.token_url.clone())
+            .form(&form)
+            .basic_auth(&self.client_id, Some(&self.client_secret));
+        // Requesting another token has no side effect.
+        Ok(self
+            .client
+            .send_idempotent(request)
+            .await?
+            .error_for_status()?
+            .json::<AuthResponse>()
+            .await?)
+    }
+}
+
+impl AuthProvider for ClientCredentials {
+    async fn access_token(&self) -> Result<String, Error> {
+        // Holding the lock during the refresh prevents concurrent requests.
+        let mut cache = self.cache.lock().await;
+        if let Some(cached) = cache.as_ref() {
+            if Instant::now() + self.refresh_margin < cached.expires_at {
+                return Ok(cached.access_token.clone());
+            }
+        }
+        let requested_at = Instant::now();
+        let response = self.request_token().await?;
+        *cache = Some(CachedToken {
+            access_token: response.access_token.clone(),
+            expires_at: requested_at + Duration::from_secs(response.expires_in),
+        });
+        Ok(response.access_token)
+    }
+}
+
+/// Select the authentication provider at runtime.
+#[derive(Clone)]
+pub enum AuthBackend {
+    /// A static token.
+    Static(StaticToken),
+    /// Tokens obtained with the client credentials flow.
+    ClientCredentials(Box<ClientCredentials>),
+}
+
+impl AuthProvider for AuthBackend {
+    async fn access_token(&self) -> Result<String, Error> {
+        match self {
+            AuthBackend::Static(p) => p.access_token().await,
+            AuthBackend::ClientCredentials(p) => p.access_token().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        matchers::{body_string_contains, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    async fn mount_token_endpoint(server: &MockServer, expires_in: u64, expected: u64) {
+        Mock::given(method("POST"))
+            .and(path("/oauth2/token"))
+            // Basic base64("client-id:client-secret").
+            .and(header(
+                "authorization",
+                "Basic Y2xpZW50LWlkOmNsaWVudC1zZWNyZXQ=",
+            ))
+            .and(body_string_contains("grant_type=client_credentials"))
+            .and(body_string_contains("scope=service_account%2Fwrite"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "token",
+                "expires_in": expires_in,
+                "token_type": "Bearer"
+            })))
+            .expect(expected)
+            .mount(server)
+            .await;
+    }
+
+    fn provider(server: &MockServer) -> ClientCredentials {
+        let token_url = format!("{}/oauth2/token", server.uri()).parse().unwrap();
+        ClientCredentials::new(token_url, "client-id", "client-secret")
+            .scope("service_account/write")
+    }
+
+    #[tokio::test]
+    async fn test_token_is_cached() {
+        let server = MockServer::start().await;
+        mount_token_endpoint(&server, 3600, 1).await;
+
+        let provider = provider(&server);
+        let clone = provider.clone();
+        assert_eq!(provider.access_token().await.unwrap(), "token");
+        assert_eq!(clone.access_token().await.unwrap(), "token");
+    }
+
+    #[tokio::test]
+    async fn test_token_is_refreshed_before_expiry() {
+        let server = MockServer::start().await;
+        mount_token_endpoint(&server, 30, 2).await;
+
+        let provider = provider(&server);
+        provider.access_token().await.unwrap();
+        provider.access_token().await.unwrap();
+    }
+
+    #[test]
+    fn test_auth_response_debug_redacts_token() {
+        let response = AuthResponse {
+            access_token: "secret".to_string(),
+            expires_in: 3600,
+            token_type: "Bearer".to_string(),
+        };
+        assert!(!format!("{response:?}").contains("secret"));
+    }
+}
@@ -1,9 +1,14 @@
-use aws_config::BehaviorVersion;
-use aws_sdk_s3::primitives::{ByteStream, SdkBody};
-use std::path::{Path, PathBuf};
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use time::{macros::format_description, OffsetDateTime};
 
-use crate::Error;
+use crate::{
+    storage::{S3Storage, Storage},
+    versioning::Calver,
+};
 
 /// Return the calver notation YY.0M for the UTC now date.
 fn calver_utc_now() -> String {
@@ -53,106 +58,185 @@ where
     p
 }
 
-/// Compute the next calver version for a series of paths.
+/// Compute the next calver version of a directory, for the year and month of
+/// the `target` version.
 ///
-/// This function does not validate the paths. Therefore different paths ending with a
-/// valid Ubuntu-like calver format would still produce a result, even though it would
-/// not make sense.
+/// Only the directories named after a version of the same year and month are
+/// considered. The other entries are ignored.
 ///
-/// Paths are ignored if:
-///   - they are not valid unicode
-///   - they do not end with a number
-pub fn calver_next(dirs: &[PathBuf]) -> u32 {
-    let with_micro = dirs
+/// ```
+/// use bnacore::{aws::s3::calver_next, versioning::Calver};
+/// use std::path::PathBuf;
+///
+/// let target = Calver::try_from_ubuntu("24.06").unwrap();
+/// let dirs = vec![
+///     PathBuf::from("usa/texas/austin/24.05.6"),
+///     PathBuf::from("usa/texas/austin/24.06"),
+/// ];
+/// assert_eq!(calver_next(&target, &dirs).to_ubuntu(), "24.06.1");
+/// ```
+pub fn calver_next(target: &Calver, dirs: &[PathBuf]) -> Calver {
+    let existing = dirs
         .iter()
         .filter_map(|d| d.file_name())
         .filter_map(|d| d.to_str())
-        .filter(|d| d.chars().filter(|c| *c == '.').count() == 2)
-        .filter_map(|d| {
-            d.split('.')
-                .collect::<Vec<&str>>()
-                .last()
-                .unwrap()
-                .parse::<u32>()
-                .ok()
-        })
-        .collect::<Vec<u32>>();
-
-    // If there is no directory with a micro part, create the first one.
-    // Otherwise get the highest micro and increment it.
-    match with_micro.iter().max() {
-        Some(micro) => micro + 1,
-        None => 1,
-    }
+        .filter_map(|d| Calver::try_from_ubuntu(d).ok())
+        .collect::<Vec<Calver>>();
+    target.next(&existing)
 }
 
-/// Create S3 directories in a sepecific bucket, following the PFB convention.
-pub async fn create_calver_s3_directories(
-    bucket_name: &str,
+/// Maximum number of attempts to claim a calver directory.
+const MAX_CALVER_CLAIM_ATTEMPTS: usize = 10;
+
+/// Create calver directories in a storage, following the PFB convention.
+///
+/// The directory is claimed atomically by creating its marker object only if
+/// it does not exist yet. If another process claims the same directory first,
+/// the existing directories are listed again and the next version is tried.
+///
+/// Returns the path of the directory which was created.
+pub async fn create_calver_directories<S: Storage>(
+    storage: &S,
     country: &str,
     city: &str,
     region: Option<&str>,
 ) -> Result<PathBuf, crate::Error> {
     // Get the base path.
     let s3_dir = calver_base::<PathBuf>(country, city, region, None, None);
-    let mut s3_dir_str = s3_dir.to_str().unwrap().to_string();
-
-    // Configure the S3 client.
-    let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let client = aws_sdk_s3::Client::new(&config);
-
-    // List the existing directory matching the base path.
-    let mut response = client
-        .list_objects_v2()
-        .bucket(bucket_name.to_owned())
-        .prefix(s3_dir_str.clone())
-        .into_paginator()
-        .send();
-
-    // Filter the directories.
-    let mut matches: Vec<String> = Vec::new();
-    while let Some(result) = response.next().await {
-        match result {
-            Ok(output) => {
-                for object in output.contents() {
-                    if let Some(key) = &object.key {
-                        if key.ends_with('/') {
-                            matches.push(key.to_string());
-                        }
-                    }
-                }
-            }
-            Err(err) => {
-                eprintln!("{err:?}")
-            }
+    let base = s3_dir.to_str().unwrap().to_string();
+    let parent = s3_dir
+        .parent()
+        .expect("the calver base path must have a parent")
+        .to_path_buf();
+    let target = Calver::try_from_ubuntu(&calver_utc_now())
+        .expect("the current date must be a valid calver");
+
+    for _ in 0..MAX_CALVER_CLAIM_ATTEMPTS {
+        // List the existing directories directly under the parent path, for
+        // the current month.
+        let dirs = storage
+            .list(&base)
+            .await?
+            .iter()
+            .filter_map(|key| Path::new(key).strip_prefix(&parent).ok())
+            .filter_map(|relative| relative.components().next())
+            .map(|first| parent.join(first))
+            .collect::<Vec<PathBuf>>();
+
+        // Get the next calver version.
+        let calver = calver_next(&target, &dirs);
+        let s3_dir_str = parent
+            .join(calver.to_ubuntu())
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        // Claim the folder object.
+        if storage
+            .put_if_absent(&format!("{s3_dir_str}/"), Vec::new())
+            .await?
+        {
+            return Ok(PathBuf::from(s3_dir_str));
         }
     }
+    Err(crate::Error::Internal(format!(
+        "cannot claim a calver directory for `{base}` after {MAX_CALVER_CLAIM_ATTEMPTS} attempts"
+    )))
+}
 
-    // Get the next calver version if necessary.
-    if !matches.is_empty() {
-        let dirs = matches.iter().map(PathBuf::from).collect::<Vec<PathBuf>>();
-        let revision = calver_next(&dirs);
-        s3_dir_str.push('.');
-        s3_dir_str.push_str(revision.to_string().as_str());
-    }
+/// Create S3 directories in a sepecific bucket, following the PFB convention.
+pub async fn create_calver_s3_directories(
+    bucket_name: &str,
+    country: &str,
+    city: &str,
+    region: Option<&str>,
+) -> Result<PathBuf, crate::Error> {
+    let storage = S3Storage::from_env(bucket_name).await;
+    create_calver_directories(&storage, country, city, region).await
+}
 
-    // Create the folder object.
-    let res = client
-        .put_object()
-        .bucket(bucket_name)
-        .key(format!("{s3_dir_str}/",))
-        .body(ByteStream::new(SdkBody::from("")))
-        .send()
-        .await;
-    match res {
-        Ok(_) => Ok(PathBuf::from(s3_dir_str)),
-        Err(e) => Err(Error::BNAAWS(super::AWSError::S3Error(e.to_string()))),
+/// Represent a presigned link to an analysis artifact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArtifactLink {
+    /// Path of the artifact, relative to the calver directory.
+    pub artifact: String,
+    /// Key of the artifact in the bucket.
+    pub key: String,
+    /// URL granting a temporary read access to the artifact.
+    pub url: String,
+    /// Expiration date of the URL.
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+}
+
+/// Return the most recent calver directory of a city, if any.
+pub async fn latest_calver_directory<S: Storage>(
+    storage: &S,
+    country: &str,
+    city: &str,
+    region: Option<&str>,
+) -> Result<Option<PathBuf>, crate::Error> {
+    let parent = calver_base::<PathBuf>(country, city, region, Some(""), None);
+    let prefix = format!("{}/", parent.to_str().unwrap().trim_end_matches('/'));
+    let latest = storage
+        .list(&prefix)
+        .await?
+        .iter()
+        .filter_map(|key| key.strip_prefix(&prefix)?.split('/').next())
+        .filter_map(|name| Calver::try_from_ubuntu(name).ok())
+        .max();
+    Ok(latest.map(|calver| parent.join(calver.to_ubuntu())))
+}
+
+/// Generate presigned GET URLs for all the artifacts of a calver directory.
+///
+/// The artifacts are listed recursively, and the directory markers are
+/// skipped. The URLs expire after `expires_in`.
+///
+/// ```no_run
+/// # use color_eyre::{eyre::Report, Result};
+/// use bnacore::{aws::s3::presign_calver_artifacts, storage::StorageBackend};
+/// use std::{path::Path, time::Duration};
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Report> {
+/// let storage = StorageBackend::from_env("brokenspoke-analyzer").await;
+/// let links = presign_calver_artifacts(
+///     &storage,
+///     Path::new("usa/texas/austin/24.05"),
+///     Duration::from_secs(7 * 24 * 3600),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn presign_calver_artifacts<S: Storage>(
+    storage: &S,
+    calver_dir: &Path,
+    expires_in: Duration,
+) -> Result<Vec<ArtifactLink>, crate::Error> {
+    let prefix = format!("{}/", calver_dir.to_str().unwrap().trim_end_matches('/'));
+    let expires_at = OffsetDateTime::now_utc() + expires_in;
+    let mut links: Vec<ArtifactLink> = Vec::new();
+    for key in storage.list(&prefix).await? {
+        if key.ends_with('/') {
+            continue;
+        }
+        let url = storage.presign(&key, expires_in).await?;
+        links.push(ArtifactLink {
+            artifact: key[prefix.len()..].to_string(),
+            key,
+            url,
+            expires_at,
+        });
     }
+    Ok(links)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::LocalStorage;
     use rstest::rstest;
 
     #[rstest]
@@ -189,11 +273,104 @@ mod tests {
     }
 
     #[rstest]
-    #[case(vec![PathBuf::from("country/region/city/22.01")], 1)]
-    #[case(vec![PathBuf::from("country/region/city/22.01.6")], 7)]
-    #[case(vec![PathBuf::from("country/region/city/22.02"), PathBuf::from("country/region/city/22.02.1"), PathBuf::from("country/region/city/22.02.2")], 3)]
-    fn test_calver_next(#[case] dirs: Vec<PathBuf>, #[case] expected: u32) {
-        let actual = calver_next(&dirs);
-        assert_eq!(actual, expected)
+    #[case::first_of_the_month("24.06", vec![], "24.06")]
+    #[case::month_rollover("24.06", vec!["24.05", "24.05.6"], "24.06")]
+    #[case::year_rollover("25.01", vec!["24.12", "24.12.3"], "25.01")]
+    #[case::rerun("24.06", vec!["24.05.6", "24.06"], "24.06.1")]
+    #[case::reruns("24.06", vec!["24.06", "24.06.1", "24.06.2"], "24.06.3")]
+    #[case::numeric_micro("24.06", vec!["24.06.9", "24.06.10"], "24.06.11")]
+    #[case::same_month_last_year("24.06", vec!["23.06.4"], "24.06")]
+    #[case::non_calver_entries("24.06", vec!["24.06", "scores.csv", "24.06.x", "latest"], "24.06.1")]
+    fn test_calver_next(#[case] target: &str, #[case] dirs: Vec<&str>, #[case] expected: &str) {
+        let target = Calver::try_from_ubuntu(target).unwrap();
+        let dirs = dirs
+            .iter()
+            .map(|d| PathBuf::from("country/region/city").join(d))
+            .collect::<Vec<PathBuf>>();
+        let actual = calver_next(&target, &dirs);
+        assert_eq!(actual.to_ubuntu(), expected)
+    }
+
+    #[tokio::test]
+    async fn test_create_calver_directories() {
+        let root = std::env::temp_dir().join(format!("bnacore-calver-{}", std::process::id()));
+        let storage = LocalStorage::new(&root);
+        let first = create_calver_directories(&storage, "USA", "Austin", Some("Texas"))
+            .await
+            .unwrap();
+        let second = create_calver_directories(&storage, "USA", "Austin", Some("Texas"))
+            .await
+            .unwrap();
+        let calver = calver_utc_now();
+        assert_eq!(first, PathBuf::from(format!("usa/texas/austin/{calver}")));
+        assert_eq!(
+            second,
+            PathBuf::from(format!("usa/texas/austin/{calver}.1"))
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_presign_calver_artifacts() {
+        let root = std::env::temp_dir().join(format!("bnacore-presign-{}", std::process::id()));
+        let storage = LocalStorage::new(&root);
+        for key in [
+            "usa/texas/austin/24.05.9/",
+            "usa/texas/austin/24.05.10/",
+            "usa/texas/austin/24.05.10/austin.zip",
+            "usa/texas/austin/24.05.10/results/scores.csv",
+            "usa/texas/austin-tx/25.01/",
+        ] {
+            storage.put(key, Vec::new()).await.unwrap();
+        }
+
+        let latest = latest_calver_directory(&storage, "USA", "Austin", Some("Texas"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest, PathBuf::from("usa/texas/austin/24.05.10"));
+        assert!(
+            latest_calver_directory(&storage, "USA", "Houston", Some("Texas"))
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let links = presign_calver_artifacts(&storage, &latest, Duration::from_secs(3600))
+            .await
+            .unwrap();
+        let artifacts = links
+            .iter()
+            .map(|l| l.artifact.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(artifacts, vec!["austin.zip", "results/scores.csv"]);
+        assert!(links[0].url.ends_with("/24.05.10/austin.zip"));
+        assert!(links[0].expires_at > OffsetDateTime::now_utc());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_create_calver_directories_concurrently() {
+        let root =
+            std::env::temp_dir().join(format!("bnacore-calver-concurrent-{}", std::process::id()));
+        let storage = LocalStorage::new(&root);
+        let tasks = (0..8)
+            .map(|_| {
+                let storage = storage.clone();
+                tokio::spawn(async move {
+                    create_calver_directories(&storage, "Spain", "Valencia", None)
+                        .await
+                        .unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut dirs = Vec::new();
+        for task in tasks {
+            dirs.push(task.await.unwrap());
+        }
+        dirs.sort();
+        dirs.dedup();
+        assert_eq!(dirs.len(), 8);
+        std::fs::remove_dir_all(root).unwrap();
     }
 }
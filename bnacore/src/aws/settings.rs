@@ -0,0 +1,430 @@
+//! Cache the parameters and secrets, and load them into typed settings.
+//!
+//! The parameters and the secrets are cached in-process for a limited time,
+//! configurable with the `BNA_CONFIG_TTL` environment variable (in seconds).
+//! A single [`CachedProvider`] is shared by the whole process, therefore the
+//! provider and its HTTP client are only created once.
+use super::{
+    config::ConfigProvider, config::Provider, AWSError, SSMParameter, SecretValue, SecretVersion,
+};
+use crate::{serde_util::struct_fields, Error};
+use serde::de::{
+    value::{MapDeserializer, SeqDeserializer},
+    DeserializeOwned, Deserializer, IntoDeserializer, Visitor,
+};
+use std::{
+    collections::HashMap,
+    env,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::sync::OnceCell;
+
+/// Environment variable used to override the cache TTL, in seconds.
+pub const TTL_VARIABLE: &str = "BNA_CONFIG_TTL";
+/// Default time to live of the cached values.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+/// Process-wide cached provider.
+static PROVIDER: OnceCell<CachedProvider<Provider>> = OnceCell::const_new();
+
+/// Represent a cached value.
+#[derive(Debug, Clone)]
+struct Entry<T> {
+    value: T,
+    expires_at: Instant,
+}
+
+/// Cache the values retrieved by a provider.
+pub struct CachedProvider<P> {
+    /// Underlying provider.
+    pub provider: P,
+    /// Time to live of the cached values.
+    pub ttl: Duration,
+    parameters: Mutex<HashMap<String, Entry<SSMParameter>>>,
+    secrets: Mutex<HashMap<String, Entry<SecretValue>>>,
+}
+
+impl<P> CachedProvider<P> {
+    /// Wrap a provider, caching its values for `ttl`.
+    pub fn new(provider: P, ttl: Duration) -> Self {
+        Self {
+            provider,
+            ttl,
+            parameters: Mutex::new(HashMap::new()),
+            secrets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Remove all the cached values.
+    pub fn clear(&self) {
+        self.parameters.lock().unwrap().clear();
+        self.secrets.lock().unwrap().clear();
+    }
+}
+
+/// Return a cached value if it has not expired yet.
+fn lookup<T: Clone>(cache: &Mutex<HashMap<String, Entry<T>>>, key: &str) -> Option<T> {
+    let cache = cache.lock().unwrap();
+    cache
+        .get(key)
+        .filter(|entry| entry.expires_at > Instant::now())
+        .map(|entry| entry.value.clone())
+}
+
+/// Store a value in the cache.
+fn store<T>(cache: &Mutex<HashMap<String, Entry<T>>>, key: &str, value: T, ttl: Duration) {
+    cache.lock().unwrap().insert(
+        key.to_string(),
+        Entry {
+            value,
+            expires_at: Instant::now() + ttl,
+        },
+    );
+}
+
+impl<P: ConfigProvider + Sync> ConfigProvider for CachedProvider<P> {
+    async fn get_parameter(&self, name: &str) -> Result<SSMParameter, Error> {
+        if let Some(parameter) = lookup(&self.parameters, name) {
+            return Ok(parameter);
+        }
+        let parameter = self.provider.get_parameter(name).await?;
+        store(&self.parameters, name, parameter.clone(), self.ttl);
+        Ok(parameter)
+    }
+
+    async fn get_secret_version(
+        &self,
+        secret_id: &str,
+        version: SecretVersion<'_>,
+    ) -> Result<SecretValue, Error> {
+        let key = format!("{secret_id}@{version}");
+        if let Some(secret) = lookup(&self.secrets, &key) {
+            return Ok(secret);
+        }
+        let secret = self.provider.get_secret_version(secret_id, version).await?;
+        store(&self.secrets, &key, secret.clone(), self.ttl);
+        Ok(secret)
+    }
+}
+
+/// Return the cache TTL from the environment.
+fn ttl_from_env() -> Result<Duration, Error> {
+    match env::var(TTL_VARIABLE) {
+        Ok(ttl) => ttl.parse::<u64>().map(Duration::from_secs).map_err(|e| {
+            Error::InvalidArgument(format!("invalid `{TTL_VARIABLE}` value `{ttl}`: {e}"))
+        }),
+        Err(_) => Ok(DEFAULT_TTL),
+    }
+}
+
+/// Return the process-wide cached provider.
+///
+/// The provider is selected with [`Provider::from_env`] the first time this
+/// function is called.
+pub async fn cached_provider() -> Result<&'static CachedProvider<Provider>, Error> {
+    PROVIDER
+        .get_or_try_init(|| async {
+            Ok(CachedProvider::new(
+                Provider::from_env().await?,
+                ttl_from_env()?,
+            ))
+        })
+        .await
+}
+
+/// Value of a parameter, deserialized according to the type of its field.
+///
+/// The environment variables cannot distinguish the `StringList` parameters,
+/// therefore a `String` parameter is split on commas when its field is a
+/// sequence.
+enum ParameterValue {
+    String(String),
+    StringList(Vec<String>),
+}
+
+impl ParameterValue {
+    /// Return the values of the parameter as a list.
+    fn into_list(self) -> Vec<String> {
+        match self {
+            ParameterValue::String(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+                .collect(),
+            ParameterValue::StringList(values) => values,
+        }
+    }
+}
+
+/// Parse a `String` parameter into the primitive type of its field.
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self {
+                    ParameterValue::String(value) => visitor
+                        .$visit(value.trim().parse().map_err(serde::de::Error::custom)?),
+                    values => values.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ParameterValue {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            ParameterValue::String(value) => visitor.visit_string(value),
+            ParameterValue::StringList(values) => {
+                visitor.visit_seq(SeqDeserializer::new(values.into_iter()))
+            }
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(SeqDeserializer::new(self.into_list().into_iter()))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct tuple tuple_struct map
+        struct enum identifier ignored_any
+    }
+}
+
+impl IntoDeserializer<'_, serde_json::Error> for ParameterValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+/// Load typed settings from the parameters of a provider.
+///
+/// Each field of the settings is read from the parameter with the same name,
+/// therefore the struct is usually annotated with
+/// `#[serde(rename_all = "SCREAMING_SNAKE_CASE")]`. `StringList` parameters, as
+/// well as comma-separated `String` parameters, can be deserialized into a
+/// `Vec<String>`, missing parameters into an `Option`, and the numbers and
+/// booleans are parsed from their strings.
+///
+/// ```no_run
+/// # use color_eyre::{eyre::Report, Result};
+/// use bnacore::aws::settings::{cached_provider, settings_from};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+/// struct Settings {
+///     bna_bucket: String,
+///     public_subnets: Vec<String>,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Report> {
+/// let settings: Settings = settings_from(cached_provider().await?).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn settings_from<T, P>(provider: &P) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+    P: ConfigProvider,
+{
+    let mut values = Vec::new();
+    for field in struct_fields::<T>() {
+        match provider.get_parameter(field).await {
+            Ok(parameter) => {
+                let value = match parameter.parameter.values() {
+                    Some(values) => ParameterValue::StringList(values),
+                    None => ParameterValue::String(parameter.parameter.value),
+                };
+                values.push((*field, value));
+            }
+            Err(Error::BNAAWS(AWSError::ParameterNotFound(_))) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let deserializer: MapDeserializer<_, serde_json::Error> =
+        MapDeserializer::new(values.into_iter());
+    Ok(T::deserialize(deserializer)?)
+}
+
+/// Load typed settings using the process-wide cached provider.
+pub async fn load_settings<T: DeserializeOwned>() -> Result<T, Error> {
+    settings_from(cached_provider().await?).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aws::{config::LocalProvider, ParameterType};
+    use serde::Deserialize;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Provider counting the number of calls.
+    #[derive(Default)]
+    struct CountingProvider {
+        calls: AtomicUsize,
+        inner: LocalProvider,
+    }
+
+    impl ConfigProvider for CountingProvider {
+        async fn get_parameter(&self, name: &str) -> Result<SSMParameter, Error> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.inner.get_parameter(name).await
+        }
+
+        async fn get_secret_version(
+            &self,
+            secret_id: &str,
+            version: SecretVersion<'_>,
+        ) -> Result<SecretValue, Error> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.inner.get_secret_version(secret_id, version).await
+        }
+    }
+
+    fn counting_provider() -> CountingProvider {
+        CountingProvider {
+            calls: AtomicUsize::new(0),
+            inner: LocalProvider::from_toml(
+                r#"
+                [parameters]
+                BNA_TEST_BUCKET = "brokenspoke-analyzer"
+                BNA_TEST_SUBNETS = ["subnet-1", "subnet-2"]
+                "#,
+            )
+            .unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit() {
+        let cache = CachedProvider::new(counting_provider(), DEFAULT_TTL);
+        cache.get_parameter("BNA_TEST_BUCKET").await.unwrap();
+        cache.get_parameter("BNA_TEST_BUCKET").await.unwrap();
+        assert_eq!(cache.provider.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache_expired() {
+        let cache = CachedProvider::new(counting_provider(), Duration::ZERO);
+        cache.get_parameter("BNA_TEST_BUCKET").await.unwrap();
+        cache.get_parameter("BNA_TEST_BUCKET").await.unwrap();
+        assert_eq!(cache.provider.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_settings_from() {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+        struct Settings {
+            bna_test_bucket: String,
+            bna_test_subnets: Vec<String>,
+            bna_test_missing: Option<String>,
+        }
+
+        let provider = counting_provider();
+        let parameter = provider.get_parameter("BNA_TEST_SUBNETS").await.unwrap();
+        assert_eq!(parameter.parameter.r#type, ParameterType::StringList);
+        let settings: Settings = settings_from(&provider).await.unwrap();
+        assert_eq!(settings.bna_test_bucket, "brokenspoke-analyzer");
+        assert_eq!(settings.bna_test_subnets, vec!["subnet-1", "subnet-2"]);
+        assert!(settings.bna_test_missing.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_settings_from_numbers() {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+        struct Settings {
+            bna_test_max: usize,
+            bna_test_min_cu: Option<f32>,
+            bna_test_timeout: i64,
+            bna_test_enabled: bool,
+        }
+
+        let provider = LocalProvider::from_dotenv(
+            r#"
+            BNA_TEST_MAX=20
+            BNA_TEST_MIN_CU=0.25
+            BNA_TEST_TIMEOUT=-1
+            BNA_TEST_ENABLED=true
+            "#,
+        );
+        let settings: Settings = settings_from(&provider).await.unwrap();
+        assert_eq!(settings.bna_test_max, 20);
+        assert_eq!(settings.bna_test_min_cu, Some(0.25));
+        assert_eq!(settings.bna_test_timeout, -1);
+        assert!(settings.bna_test_enabled);
+    }
+
+    #[tokio::test]
+    async fn test_settings_from_dotenv() {
+        // Settings of the bna-fargate-run lambda.
+        #[derive(Deserialize)]
+        #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+        struct Settings {
+            bna_api_hostname: String,
+            bna_bucket: String,
+            bna_cluster_arn: String,
+            bna_task_definition: String,
+            bna_task_security_group: String,
+            public_subnets: Vec<String>,
+        }
+
+        let provider = LocalProvider::from_dotenv(
+            r#"
+            BNA_API_HOSTNAME=https://api.peopleforbikes.xyz
+            BNA_BUCKET=brokenspoke-analyzer
+            BNA_CLUSTER_ARN=arn:aws:ecs:us-west-2:123456789012:cluster/bna
+            BNA_TASK_DEFINITION=bna:1
+            BNA_TASK_SECURITY_GROUP=sg-1
+            PUBLIC_SUBNETS="subnet-1, subnet-2"
+            "#,
+        );
+        let settings: Settings = settings_from(&provider).await.unwrap();
+        assert_eq!(settings.bna_api_hostname, "https://api.peopleforbikes.xyz");
+        assert_eq!(settings.bna_bucket, "brokenspoke-analyzer");
+        assert_eq!(
+            settings.bna_cluster_arn,
+            "arn:aws:ecs:us-west-2:123456789012:cluster/bna"
+        );
+        assert_eq!(settings.bna_task_definition, "bna:1");
+        assert_eq!(settings.bna_task_security_group, "sg-1");
+        assert_eq!(settings.public_subnets, vec!["subnet-1", "subnet-2"]);
+    }
+}
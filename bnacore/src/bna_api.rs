@@ -0,0 +1,425 @@
+//! Client of the BNA REST API.
+//!
+//! The client builds the URLs of the resources from their path segments, which
+//! are percent-encoded, since the city names may contain spaces or other
+//! reserved characters.
+use crate::{
+    auth::{AuthBackend, AuthProvider, StaticToken},
+    http::{http_client, HttpClient},
+    Error,
+};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use rust_decimal::Decimal;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use time::OffsetDateTime;
+use url::Url;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNASummary {
+    pub bna_uuid: Uuid,
+    pub version: String,
+    pub city_id: Uuid,
+    pub score: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNAInfrastructure {
+    pub low_stress_miles: Option<f64>,
+    pub high_stress_miles: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNARecreation {
+    pub community_centers: Option<f64>,
+    pub parks: Option<f64>,
+    pub recreation_trails: Option<f64>,
+    pub score: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNAOpportunity {
+    pub employment: Option<f64>,
+    pub higher_education: Option<f64>,
+    pub k12_education: Option<f64>,
+    pub technical_vocational_college: Option<f64>,
+    pub score: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNACoreServices {
+    pub dentists: Option<f64>,
+    pub doctors: Option<f64>,
+    pub grocery: Option<f64>,
+    pub hospitals: Option<f64>,
+    pub pharmacies: Option<f64>,
+    pub social_services: Option<f64>,
+    pub score: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNAFeatures {
+    pub people: Option<f64>,
+    pub retail: Option<f64>,
+    pub transit: Option<f64>,
+}
+
+/// Represent the results of an analysis.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BNAPost {
+    pub core_services: BNACoreServices,
+    pub features: BNAFeatures,
+    pub infrastructure: BNAInfrastructure,
+    pub opportunity: BNAOpportunity,
+    pub recreation: BNARecreation,
+    pub summary: BNASummary,
+}
+
+/// Represent a city.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Default)]
+pub struct City {
+    pub city_id: Option<Uuid>,
+    pub country: String,
+    pub state: String,
+    pub name: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub region: Option<String>,
+    pub state_abbrev: Option<String>,
+    pub speed_limit: Option<i32>,
+    pub created_at: Option<OffsetDateTime>,
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BrokenspokeState {
+    SqsMessage,
+    Setup,
+    Analysis,
+    Cleanup,
+}
+
+/// Represent the state of an analysis pipeline.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrokenspokePipeline {
+    pub cost: Option<Decimal>,
+    pub end_time: Option<OffsetDateTime>,
+    pub fargate_task_arn: Option<String>,
+    pub neon_branch_id: Option<String>,
+    pub s3_bucket: Option<String>,
+    pub scheduled_trigger_id: Option<Uuid>,
+    pub sqs_message: Option<String>,
+    pub start_time: OffsetDateTime,
+    pub state: Option<BrokenspokeState>,
+    pub state_machine_id: Uuid,
+    pub torn_down: Option<bool>,
+}
+
+impl Default for BrokenspokePipeline {
+    fn default() -> Self {
+        Self {
+            cost: Default::default(),
+            end_time: Default::default(),
+            fargate_task_arn: Default::default(),
+            neon_branch_id: Default::default(),
+            s3_bucket: Default::default(),
+            scheduled_trigger_id: Default::default(),
+            sqs_message: Default::default(),
+            start_time: OffsetDateTime::now_utc(),
+            state_machine_id: Default::default(),
+            state: Default::default(),
+            torn_down: Default::default(),
+        }
+    }
+}
+
+impl BrokenspokePipeline {
+    /// Return true if the resources of the pipeline were released.
+    ///
+    /// A pipeline may have an end time before being torn down, while the
+    /// teardown still needs its database.
+    pub fn is_torn_down(&self) -> bool {
+        self.torn_down.unwrap_or_default()
+    }
+}
+
+/// BNA REST API client.
+///
+/// ```no_run
+/// # use color_eyre::{eyre::Report, Result};
+/// use bnacore::bna_api::BnaApiClient;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Report> {
+/// let api = BnaApiClient::new("https://api.peopleforbikes.xyz")?.with_access_token("token");
+/// let city = api.get_city("usa", "new mexico", "santa rosa").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BnaApiClient {
+    client: HttpClient,
+    base_url: Url,
+    auth: Option<AuthBackend>,
+}
+
+impl BnaApiClient {
+    /// Create a client for the API at `base_url`, using the process-wide HTTP
+    /// client.
+    pub fn new(base_url: &str) -> Result<Self, Error> {
+        let base_url = Url::parse(base_url)?;
+        if base_url.cannot_be_a_base() {
+            return Err(Error::InvalidArgument(format!(
+                "`{base_url}` cannot be used as the base URL of the API"
+            )));
+        }
+        Ok(Self {
+            client: http_client().clone(),
+            base_url,
+            auth: None,
+        })
+    }
+
+    /// Use another HTTP client.
+    pub fn with_http_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Authenticate the requests with the tokens of an [`AuthBackend`].
+    pub fn with_auth(mut self, auth: AuthBackend) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Authenticate the requests with a static bearer token.
+    pub fn with_access_token(self, access_token: &str) -> Self {
+        self.with_auth(AuthBackend::Static(StaticToken(access_token.into())))
+    }
+
+    /// Build the URL of a resource from its path segments.
+    ///
+    /// The segments are percent-encoded.
+    pub fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("the base URL was checked when creating the client")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    /// Add the access token to a request.
+    async fn authorize(&self, request: RequestBuilder) -> Result<RequestBuilder, Error> {
+        match &self.auth {
+            Some(auth) => Ok(request.bearer_auth(auth.access_token().await?)),
+            None => Ok(request),
+        }
+    }
+
+    /// Send an authorized request, and fail on error responses.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, Error> {
+        let request = self.authorize(request).await?;
+        Ok(self.client.send(request).await?.error_for_status()?)
+    }
+
+    /// Send an authorized request which can safely be repeated, and fail on
+    /// error responses.
+    async fn send_idempotent(&self, request: RequestBuilder) -> Result<Response, Error> {
+        let request = self.authorize(request).await?;
+        Ok(self
+            .client
+            .send_idempotent(request)
+            .await?
+            .error_for_status()?)
+    }
+
+    /// Send an authorized request and decode its JSON response.
+    async fn fetch<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, Error> {
+        Ok(self.send(request).await?.json::<T>().await?)
+    }
+
+    /// Retrieve a city, or `None` if it does not exist.
+    pub async fn get_city(
+        &self,
+        country: &str,
+        region: &str,
+        name: &str,
+    ) -> Result<Option<City>, Error> {
+        let request = self
+            .client
+            .get(self.url(&["cities", country, region, name]));
+        match self.fetch::<City>(request).await {
+            Ok(city) => Ok(Some(city)),
+            Err(Error::Reqwest(e)) if e.status() == Some(StatusCode::NOT_FOUND) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Create a city, and return it as stored by the API.
+    pub async fn create_city(&self, city: &City) -> Result<City, Error> {
+        self.fetch(self.client.post(self.url(&["cities"])).json(city))
+            .await
+    }
+
+    /// Submit the results of an analysis.
+    pub async fn post_bna(&self, bna: &BNAPost) -> Result<(), Error> {
+        self.send(self.client.post(self.url(&["bnas"])).json(bna))
+            .await?;
+        Ok(())
+    }
+
+    /// Retrieve all the pipelines.
+    pub async fn get_pipelines(&self) -> Result<Vec<BrokenspokePipeline>, Error> {
+        self.fetch(self.client.get(self.url(&["bnas", "analysis"])))
+            .await
+    }
+
+    /// Retrieve a pipeline.
+    pub async fn get_pipeline(&self, state_machine_id: Uuid) -> Result<BrokenspokePipeline, Error> {
+        let id = state_machine_id.to_string();
+        self.fetch(self.client.get(self.url(&["bnas", "analysis", &id])))
+            .await
+    }
+
+    /// Create a pipeline.
+    pub async fn create_pipeline(&self, pipeline: &BrokenspokePipeline) -> Result<(), Error> {
+        self.send(
+            self.client
+                .post(self.url(&["bnas", "analysis"]))
+                .json(pipeline),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Update the pipeline identified by [`BrokenspokePipeline::state_machine_id`].
+    ///
+    /// The update replaces the fields of a known pipeline, so it is retried on
+    /// transient errors.
+    pub async fn patch_pipeline(&self, pipeline: &BrokenspokePipeline) -> Result<(), Error> {
+        let id = pipeline.state_machine_id.to_string();
+        self.send_idempotent(
+            self.client
+                .patch(self.url(&["bnas", "analysis", &id]))
+                .json(pipeline),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use wiremock::{
+        matchers::{body_partial_json, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[rstest]
+    #[case(
+        "https://api.peopleforbikes.xyz",
+        "https://api.peopleforbikes.xyz/cities/usa/new%20mexico/santa%20rosa"
+    )]
+    #[case(
+        "https://api.peopleforbikes.xyz/",
+        "https://api.peopleforbikes.xyz/cities/usa/new%20mexico/santa%20rosa"
+    )]
+    #[case(
+        "https://example.com/staging",
+        "https://example.com/staging/cities/usa/new%20mexico/santa%20rosa"
+    )]
+    fn test_url(#[case] base_url: &str, #[case] expected: &str) {
+        let api = BnaApiClient::new(base_url).unwrap();
+        assert_eq!(
+            api.url(&["cities", "usa", "new mexico", "santa rosa"])
+                .as_str(),
+            expected
+        )
+    }
+
+    #[test]
+    fn test_url_encodes_slashes() {
+        let api = BnaApiClient::new("https://api.peopleforbikes.xyz").unwrap();
+        assert_eq!(
+            api.url(&["cities", "usa", "texas", "a/b"]).path(),
+            "/cities/usa/texas/a%2Fb"
+        )
+    }
+
+    #[test]
+    fn test_new_invalid_base_url() {
+        assert!(BnaApiClient::new("mailto:bna@peopleforbikes.org").is_err())
+    }
+
+    #[tokio::test]
+    async fn test_get_city() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/cities/usa/new%20mexico/santa%20rosa"))
+            .and(header("authorization", "Bearer token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "city_id": "da4d5cb1-5a2e-4c8e-a3b4-3d5ae3e5a1b5",
+                "country": "usa",
+                "state": "new mexico",
+                "name": "santa rosa"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let city = BnaApiClient::new(&server.uri())
+            .unwrap()
+            .with_access_token("token")
+            .get_city("usa", "new mexico", "santa rosa")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(city.name, "santa rosa");
+    }
+
+    #[tokio::test]
+    async fn test_get_missing_city() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let city = BnaApiClient::new(&server.uri())
+            .unwrap()
+            .get_city("usa", "texas", "austin")
+            .await
+            .unwrap();
+        assert_eq!(city, None);
+    }
+
+    #[tokio::test]
+    async fn test_patch_pipeline() {
+        let server = MockServer::start().await;
+        let state_machine_id = Uuid::parse_str("9ff90cac-0cf5-4923-897f-4416df5e7328").unwrap();
+        Mock::given(method("PATCH"))
+            .and(path(format!("/bnas/analysis/{state_machine_id}")))
+            .and(header("authorization", "Bearer token"))
+            .and(body_partial_json(serde_json::json!({"torn_down": true})))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let pipeline = BrokenspokePipeline {
+            state_machine_id,
+            torn_down: Some(true),
+            ..Default::default()
+        };
+        BnaApiClient::new(&server.uri())
+            .unwrap()
+            .with_access_token("token")
+            .patch_pipeline(&pipeline)
+            .await
+            .unwrap();
+    }
+}
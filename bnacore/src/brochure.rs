@@ -0,0 +1,512 @@
+//! Render multi-page brochures described by a manifest.
+//!
+//! A manifest lists the pages of a brochure in order. Each page is either a
+//! MiniJinja SVG template rendered with the data of a record, or a static SVG
+//! or PDF document included as-is. All the pages are then combined into a
+//! single PDF document.
+//!
+//! The templated pages can be translated with the catalogs of the `catalog`
+//! directory. They are rendered in the default locale unless another
+//! localization is given.
+//!
+//! The files of a brochure can also be embedded into a binary with
+//! [`Manifest::embed`], in which case they are not read from the disk.
+//!
+//! ```toml
+//! name = "scorecard"
+//! output = "{{ co | sanitize }}-{{ st | sanitize }}-{{ ci | sanitize }}.pdf"
+//! catalog = "../locales"
+//!
+//! [[pages]]
+//! kind = "template"
+//! path = "../visuals/template-scorecard-pg1-{version}.svg"
+//! version = "v23.2"
+//!
+//! [[pages]]
+//! kind = "static"
+//! path = "../visuals/template-scorecard-pg2-{version}.svg"
+//! version = "v23.1"
+//! ```
+use crate::{
+    combine::combine_mem,
+    i18n::{Catalog, LocaleSelection, Localization, DEFAULT_LOCALE},
+    template::{export, render_record, render_record_with, Exporter, RenderOptions},
+    Error,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Placeholder replaced by the page version in the page paths.
+const VERSION_PLACEHOLDER: &str = "{version}";
+
+/// Describe a multi-page brochure.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Manifest {
+    /// Name of the brochure.
+    pub name: String,
+    /// MiniJinja template used to name the output files.
+    ///
+    /// It is rendered with the same record as the pages. The `.pdf` extension
+    /// is added if missing.
+    pub output: String,
+    /// Pages of the brochure, in order.
+    pub pages: Vec<Page>,
+    /// Directory of the translation catalogs, relative to the manifest.
+    #[serde(default)]
+    pub catalog: Option<String>,
+    /// Fail if a templated page references a variable missing from the record.
+    #[serde(default)]
+    pub strict: bool,
+    /// Directory used to resolve the relative page paths.
+    ///
+    /// It is set to the directory containing the manifest when the manifest is
+    /// loaded from a file.
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+    /// Files embedded into the binary, by path.
+    ///
+    /// The paths are resolved the same way as the page paths, and the embedded
+    /// files are used instead of the files on disk.
+    #[serde(skip)]
+    pub embedded: HashMap<PathBuf, &'static [u8]>,
+}
+
+/// Describe how a page is produced.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageKind {
+    /// SVG template rendered with the record data.
+    Template,
+    /// SVG or PDF document included as-is.
+    Static,
+}
+
+/// Describe a single page of a brochure.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Page {
+    /// How the page is produced.
+    pub kind: PageKind,
+    /// Path of the page source, relative to the manifest.
+    ///
+    /// The `{version}` placeholder is replaced by the page version.
+    pub path: String,
+    /// Version of the page source.
+    pub version: Option<String>,
+}
+
+/// Convert SVG documents to PDF.
+pub trait PdfConverter {
+    /// Convert an SVG document to a PDF document.
+    fn convert(&self, svg: &str) -> Result<Vec<u8>, Error>;
+}
+
+impl PdfConverter for Exporter {
+    /// Convert an SVG document to PDF by using the external exporter program.
+    ///
+    /// The SVG document is written to a temporary file, which is removed along
+    /// with the generated PDF once the PDF has been read.
+    fn convert(&self, svg: &str) -> Result<Vec<u8>, Error> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let src = std::env::temp_dir().join(format!(
+            "bnacore-brochure-{}-{}.svg",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&src, svg)?;
+        export(*self, std::slice::from_ref(&src));
+        let pdf = src.with_extension("pdf");
+        let converted = fs::read(&pdf);
+        let _ = fs::remove_file(&src);
+        let _ = fs::remove_file(&pdf);
+        Ok(converted?)
+    }
+}
+
+impl Page {
+    /// Return the path of the page source, with the version placeholder
+    /// replaced.
+    ///
+    /// ```
+    /// use bnacore::brochure::{Page, PageKind};
+    /// use std::path::{Path, PathBuf};
+    ///
+    /// let page = Page {
+    ///     kind: PageKind::Template,
+    ///     path: "template-scorecard-pg1-{version}.svg".to_string(),
+    ///     version: Some("v23.2".to_string()),
+    /// };
+    /// assert_eq!(
+    ///     page.resolve(Path::new("visuals")),
+    ///     PathBuf::from("visuals/template-scorecard-pg1-v23.2.svg")
+    /// );
+    /// ```
+    pub fn resolve(&self, base_dir: &Path) -> PathBuf {
+        let path = match &self.version {
+            Some(version) => self.path.replace(VERSION_PLACEHOLDER, version),
+            None => self.path.clone(),
+        };
+        base_dir.join(path)
+    }
+}
+
+impl Manifest {
+    /// Load a manifest from a TOML or JSON file, based on its extension.
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path)?;
+        let mut manifest = match path.extension().and_then(OsStr::to_str) {
+            Some("toml") => Self::from_toml(&content)?,
+            Some("json") => Self::from_json(&content)?,
+            _ => {
+                return Err(Error::InvalidArgument(format!(
+                    "the manifest must be a TOML or a JSON file, got {}",
+                    path.display()
+                )))
+            }
+        };
+        manifest.base_dir = path.parent().map_or_else(PathBuf::new, |p| p.to_path_buf());
+        Ok(manifest)
+    }
+
+    /// Parse a manifest from a TOML string.
+    pub fn from_toml(content: &str) -> Result<Self, Error> {
+        Ok(toml::from_str::<Manifest>(content)?)
+    }
+
+    /// Parse a manifest from a JSON string.
+    pub fn from_json(content: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str::<Manifest>(content)?)
+    }
+
+    /// Return the name of the output file for a specific record.
+    ///
+    /// The name must not contain any path separator, so that the file cannot be
+    /// written outside of the output directory.
+    pub fn output_name<S: Serialize>(&self, record: &S) -> Result<String, Error> {
+        let mut name = render_record(&self.output, record)?;
+        if name.contains(['/', '\\']) {
+            return Err(Error::InvalidArgument(format!(
+                "the output name `{name}` must not contain a path separator"
+            )));
+        }
+        if !name.ends_with(".pdf") {
+            name.push_str(".pdf");
+        }
+        Ok(name)
+    }
+
+    /// Embed a file into the manifest.
+    ///
+    /// The `path` is relative to the manifest, like the page paths and the
+    /// catalog directory.
+    pub fn embed(mut self, path: &str, content: &'static [u8]) -> Self {
+        self.embedded.insert(self.base_dir.join(path), content);
+        self
+    }
+
+    /// Read a file, from the embedded files first, then from the disk.
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        match self.embedded.get(path) {
+            Some(content) => Ok(content.to_vec()),
+            None => Ok(fs::read(path)?),
+        }
+    }
+
+    /// Read a text file, from the embedded files first, then from the disk.
+    fn read_to_string(&self, path: &Path) -> Result<String, Error> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|e| Error::InvalidArgument(format!("{} is not UTF-8: {e}", path.display())))
+    }
+
+    /// Create a localization from the catalog of the manifest, if any.
+    ///
+    /// The catalog is loaded from the embedded `<locale>.ftl` files of the
+    /// catalog directory if there are any, or from the directory itself.
+    pub fn localization(&self, selection: LocaleSelection) -> Result<Option<Localization>, Error> {
+        let Some(catalog) = &self.catalog else {
+            return Ok(None);
+        };
+        let dir = self.base_dir.join(catalog);
+        let embedded = self
+            .embedded
+            .iter()
+            .filter(|(path, _)| {
+                path.parent() == Some(dir.as_path()) && path.extension() == Some(OsStr::new("ftl"))
+            })
+            .collect::<Vec<_>>();
+        let catalog = if embedded.is_empty() {
+            Catalog::from_dir(&dir, DEFAULT_LOCALE)?
+        } else {
+            let mut catalog = Catalog::new(DEFAULT_LOCALE);
+            for (path, _) in embedded {
+                if let Some(locale) = path.file_stem().and_then(OsStr::to_str) {
+                    catalog.add_resource(locale, &self.read_to_string(path)?)?;
+                }
+            }
+            catalog
+        };
+        Ok(Some(Localization::new(catalog, selection)))
+    }
+
+    /// Render all the pages for a specific record and combine them into a
+    /// single PDF document.
+    ///
+    /// The templated pages are localized with `localization`, or with the
+    /// default locale of the manifest catalog if it is not specified. They are
+    /// rendered in strict mode if the manifest requires it.
+    pub fn render<S, C>(
+        &self,
+        record: &S,
+        converter: &C,
+        localization: Option<&Localization>,
+    ) -> Result<Vec<u8>, Error>
+    where
+        S: Serialize,
+        C: PdfConverter,
+    {
+        if self.pages.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "the brochure `{}` does not contain any page",
+                self.name
+            )));
+        }
+
+        // Render each page to PDF.
+        let default_localization;
+        let localization = match localization {
+            Some(localization) => Some(localization),
+            None => {
+                default_localization =
+                    self.localization(LocaleSelection::Fixed(DEFAULT_LOCALE.to_string()))?;
+                default_localization.as_ref()
+            }
+        };
+        let options = RenderOptions {
+            localization,
+            strict: self.strict,
+            ..Default::default()
+        };
+        let mut pages: Vec<Vec<u8>> = Vec::with_capacity(self.pages.len());
+        for page in &self.pages {
+            let path = page.resolve(&self.base_dir);
+            let pdf = match page.kind {
+                PageKind::Template => {
+                    let source = self.read_to_string(&path)?;
+                    let rendered = render_record_with(&source, record, &options)?;
+                    converter.convert(&rendered)?
+                }
+                PageKind::Static => match path.extension().and_then(OsStr::to_str) {
+                    Some("pdf") => self.read(&path)?,
+                    _ => converter.convert(&self.read_to_string(&path)?)?,
+                },
+            };
+            pages.push(pdf);
+        }
+
+        // Combine them.
+        let buffers = pages.iter().map(Vec::as_slice).collect::<Vec<&[u8]>>();
+        let mut document = combine_mem(&buffers)?;
+        let mut buffer: Vec<u8> = Vec::new();
+        document.save_to(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Render the brochure for a specific record into the output directory.
+    ///
+    /// Returns the path of the generated PDF file.
+    pub fn render_to_dir<S, C>(
+        &self,
+        record: &S,
+        converter: &C,
+        localization: Option<&Localization>,
+        output_dir: &Path,
+    ) -> Result<PathBuf, Error>
+    where
+        S: Serialize,
+        C: PdfConverter,
+    {
+        fs::create_dir_all(output_dir)?;
+        let output_file = output_dir.join(self.output_name(record)?);
+        fs::write(&output_file, self.render(record, converter, localization)?)?;
+        Ok(output_file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const MANIFEST: &str = r#"
+        name = "scorecard"
+        output = "{{ co | lower }}-{{ st | lower }}-{{ ci | lower | replace(' ', '_') }}"
+
+        [[pages]]
+        kind = "template"
+        path = "template-scorecard-pg1-{version}.svg"
+        version = "v23.2"
+
+        [[pages]]
+        kind = "static"
+        path = "template-scorecard-pg2-v23.1.pdf"
+    "#;
+
+    #[test]
+    fn test_parse_toml_manifest() {
+        let manifest = Manifest::from_toml(MANIFEST).unwrap();
+        assert_eq!(manifest.pages.len(), 2);
+        assert_eq!(manifest.pages[0].kind, PageKind::Template);
+        assert_eq!(manifest.pages[1].kind, PageKind::Static);
+        assert_eq!(manifest.pages[1].version, None);
+    }
+
+    #[test]
+    fn test_parse_json_manifest() {
+        let raw_json = r#"{
+          "name": "scorecard",
+          "output": "{{ ci }}",
+          "pages": [{"kind": "static", "path": "page.pdf"}]
+        }"#;
+        let manifest = Manifest::from_json(raw_json).unwrap();
+        assert_eq!(manifest.pages[0].path, "page.pdf");
+    }
+
+    #[test]
+    fn test_output_name() {
+        let manifest = Manifest::from_toml(MANIFEST).unwrap();
+        let record = HashMap::from([("co", "United States"), ("st", "TX"), ("ci", "Fort Worth")]);
+        assert_eq!(
+            manifest.output_name(&record).unwrap(),
+            "united states-tx-fort_worth.pdf"
+        );
+    }
+
+    #[test]
+    fn test_scorecard_output_name() {
+        let manifest_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../assets/brochures/scorecard.toml");
+        let manifest = Manifest::from_path(&manifest_path).unwrap();
+        let record = HashMap::from([
+            ("co", "United States"),
+            ("st", "ID"),
+            ("ci", "Coeur d'Alene"),
+        ]);
+        assert_eq!(
+            manifest.output_name(&record).unwrap(),
+            "united_states-id-coeur_dalene.pdf"
+        );
+        let record = HashMap::from([("co", "Switzerland"), ("st", "ZH"), ("ci", "Zürich")]);
+        assert_eq!(
+            manifest.output_name(&record).unwrap(),
+            "switzerland-zh-zürich.pdf"
+        );
+        let record = HashMap::from([("co", "Spain"), ("st", "../.."), ("ci", "Madrid")]);
+        assert_eq!(
+            manifest.output_name(&record).unwrap(),
+            "spain-....-madrid.pdf"
+        );
+    }
+
+    #[test]
+    fn test_output_name_with_separator() {
+        let manifest = Manifest::from_toml(MANIFEST).unwrap();
+        let record = HashMap::from([("co", "Spain"), ("st", "../.."), ("ci", "Madrid")]);
+        assert!(manifest.output_name(&record).is_err());
+    }
+
+    #[test]
+    fn test_render_without_pages() {
+        let manifest = Manifest {
+            name: "empty".to_string(),
+            output: "empty".to_string(),
+            pages: vec![],
+            catalog: None,
+            strict: false,
+            base_dir: PathBuf::new(),
+            embedded: HashMap::new(),
+        };
+        let record: HashMap<String, String> = HashMap::new();
+        assert!(manifest.render(&record, &Exporter::SVG2PDF, None).is_err());
+    }
+
+    #[test]
+    fn test_localization_from_catalog() {
+        let manifest_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../assets/brochures/scorecard.toml");
+        let manifest = Manifest::from_path(&manifest_path).unwrap();
+        let localization = manifest
+            .localization(LocaleSelection::Fixed("es-ES".to_string()))
+            .unwrap();
+        assert!(localization.is_some());
+
+        let manifest = Manifest::from_toml(MANIFEST).unwrap();
+        let localization = manifest
+            .localization(LocaleSelection::Fixed("es-ES".to_string()))
+            .unwrap();
+        assert!(localization.is_none());
+    }
+
+    #[test]
+    fn test_localized_scorecard_template() {
+        let manifest_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../assets/brochures/scorecard.toml");
+        let manifest = Manifest::from_path(&manifest_path).unwrap();
+        let source = manifest
+            .read_to_string(&manifest.pages[0].resolve(&manifest.base_dir))
+            .unwrap();
+        let record = serde_json::json!({
+            "ci": "Madrid", "co": "Spain", "st": "MD", "po": "3332035",
+            "nh": 81, "op": 87, "es": 88, "ret": 74, "rec": 86, "tr": 85,
+            "bnasc": 84, "lsm": 482, "hsm": 88
+        });
+
+        for (locale, expected) in [
+            (
+                "en-US",
+                ["Miles of high-stress roads", ">88<", ">482<", "3,332,035"],
+            ),
+            (
+                "es-ES",
+                ["Vías de alto estrés (km)", ">142<", ">776<", "3.332.035"],
+            ),
+        ] {
+            let localization = manifest
+                .localization(LocaleSelection::Fixed(locale.to_string()))
+                .unwrap();
+            let options = RenderOptions {
+                localization: localization.as_ref(),
+                strict: true,
+                ..Default::default()
+            };
+            let rendered = render_record_with(&source, &record, &options).unwrap();
+            for text in expected {
+                assert!(rendered.contains(text), "{locale}: {text} not found");
+            }
+        }
+    }
+
+    #[test]
+    fn test_embedded_files() {
+        let manifest = Manifest::from_toml(&format!("catalog = \"locales\"\n{MANIFEST}"))
+            .unwrap()
+            .embed("template-scorecard-pg1-v23.2.svg", b"<svg>{{ ci }}</svg>")
+            .embed("locales/en-US.ftl", b"score = Score");
+        let path = manifest.pages[0].resolve(&manifest.base_dir);
+        assert_eq!(
+            manifest.read_to_string(&path).unwrap(),
+            "<svg>{{ ci }}</svg>"
+        );
+        let path = manifest.pages[1].resolve(&manifest.base_dir);
+        assert!(manifest.read(&path).is_err());
+        let localization = manifest
+            .localization(LocaleSelection::Fixed("es-ES".to_string()))
+            .unwrap();
+        assert!(localization.is_some());
+    }
+}
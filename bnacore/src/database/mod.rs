@@ -0,0 +1,114 @@
+//! Provision the analysis databases on Neon or on a Postgres server.
+//!
+//! The [`DatabaseProvisioner`] trait creates and deletes the database of an
+//! analysis. The [`ProvisionerBackend`] enum selects the provisioner at
+//! runtime, which allows the pipeline to run against a local Postgres server,
+//! like the one deployed by `infra/kubernetes/postgresql`, instead of Neon.
+pub mod neon;
+pub mod postgres;
+
+use crate::Error;
+use rust_decimal::Decimal;
+use std::{env, future::Future};
+use url::Url;
+
+pub use self::{
+    neon::{NeonProvisioner, NeonSettings},
+    postgres::PostgresProvisioner,
+};
+
+/// Environment variable selecting the Postgres provisioner, by pointing to the
+/// connection URL of a role allowed to create databases.
+pub const POSTGRES_PROVISIONER_VARIABLE: &str = "BNA_POSTGRES_URL";
+
+/// Represent a provisioned database.
+///
+/// The credentials are not part of it, and must be requested with
+/// [`DatabaseProvisioner::connection_url`] when needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Database {
+    /// Identifier to use to deprovision the database.
+    pub id: String,
+    /// Hostname of the server.
+    pub host: String,
+}
+
+/// Create and delete the databases of the analyses.
+pub trait DatabaseProvisioner {
+    /// Create a database for the analysis `name`.
+    fn provision(&self, name: &str) -> impl Future<Output = Result<Database, Error>> + Send;
+
+    /// Return the connection URL of a database, including the credentials.
+    fn connection_url(
+        &self,
+        database: &Database,
+    ) -> impl Future<Output = Result<Url, Error>> + Send;
+
+    /// Estimate the cost of a database, in dollars.
+    ///
+    /// The consumption is lost with the database, so the cost must be
+    /// estimated before deprovisioning it.
+    fn cost(&self, id: &str) -> impl Future<Output = Result<Decimal, Error>> + Send;
+
+    /// Delete a database, using the [`Database::id`] returned when it was
+    /// provisioned.
+    ///
+    /// Deleting a database which does not exist is not an error.
+    fn deprovision(&self, id: &str) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+/// Select the database provisioner at runtime.
+pub enum ProvisionerBackend {
+    /// Neon branches.
+    Neon(Box<NeonProvisioner>),
+    /// Databases of a Postgres server.
+    Postgres(PostgresProvisioner),
+}
+
+impl ProvisionerBackend {
+    /// Select the provisioner from the environment.
+    ///
+    /// If the `BNA_POSTGRES_URL` variable is set, the databases are created on
+    /// the server it points to. Otherwise `neon` is called to create the Neon
+    /// provisioner.
+    pub async fn from_env<F, Fut>(neon: F) -> Result<Self, Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<NeonProvisioner, Error>>,
+    {
+        match env::var(POSTGRES_PROVISIONER_VARIABLE) {
+            Ok(url) => Ok(Self::Postgres(PostgresProvisioner::new(url.parse()?))),
+            Err(_) => Ok(Self::Neon(Box::new(neon().await?))),
+        }
+    }
+}
+
+impl DatabaseProvisioner for ProvisionerBackend {
+    async fn provision(&self, name: &str) -> Result<Database, Error> {
+        match self {
+            ProvisionerBackend::Neon(p) => p.provision(name).await,
+            ProvisionerBackend::Postgres(p) => p.provision(name).await,
+        }
+    }
+
+    async fn connection_url(&self, database: &Database) -> Result<Url, Error> {
+        match self {
+            ProvisionerBackend::Neon(p) => p.connection_url(database).await,
+            ProvisionerBackend::Postgres(p) => p.connection_url(database).await,
+        }
+    }
+
+    async fn cost(&self, id: &str) -> Result<Decimal, Error> {
+        match self {
+            ProvisionerBackend::Neon(p) => p.cost(id).await,
+            ProvisionerBackend::Postgres(p) => p.cost(id).await,
+        }
+    }
+
+    async fn deprovision(&self, id: &str) -> Result<(), Error> {
+        match self {
+            ProvisionerBackend::Neon(p) => p.deprovision(id).await,
+            ProvisionerBackend::Postgres(p) => p.deprovision(id).await,
+        }
+    }
+}
@@ -0,0 +1,466 @@
+//! Shared HTTP client with timeouts and retries.
+//!
+//! Requests failing with a transient error are retried with an exponential
+//! backoff and jitter. Transient errors are connection errors, timeouts, and
+//! the `429 Too Many Requests` and `5xx` responses. When the server sends a
+//! `Retry-After` header, its delay is used instead of the backoff.
+//!
+//! Only the idempotent requests are retried on all the transient errors. The
+//! other ones, like `POST` and `PATCH`, may have been applied even though their
+//! response was lost, so they are only retried when the connection could not
+//! be established. [`HttpClient::send_idempotent`] retries them on all the
+//! transient errors, for the calls known to be safe to repeat.
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    IntoUrl, Method, RequestBuilder, Response, StatusCode,
+};
+use std::{sync::OnceLock, time::Duration};
+use time::{macros::format_description, OffsetDateTime, PrimitiveDateTime};
+
+/// Default timeout of a whole request.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default timeout of the connection phase.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Process-wide HTTP client.
+static CLIENT: OnceLock<HttpClient> = OnceLock::new();
+
+/// Define how failed requests are retried.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the first attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each following retry.
+    pub base_delay: Duration,
+    /// Maximum delay between two attempts, including the `Retry-After` delays.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Disable the retries.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Compute the delay before a retry, with jitter.
+    ///
+    /// The delay is picked randomly between half and the full exponential
+    /// delay for this `attempt` (starting at 0).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let half = delay / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+/// Build an [`HttpClient`].
+#[derive(Debug, Clone)]
+pub struct HttpClientBuilder {
+    timeout: Duration,
+    connect_timeout: Duration,
+    retry: RetryPolicy,
+    default_headers: HeaderMap,
+}
+
+impl Default for HttpClientBuilder {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            retry: RetryPolicy::default(),
+            default_headers: HeaderMap::new(),
+        }
+    }
+}
+
+impl HttpClientBuilder {
+    /// Set the timeout of a whole request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the timeout of the connection phase.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Set the retry policy.
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Set the headers sent with every request.
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.default_headers = headers;
+        self
+    }
+
+    /// Build the client.
+    pub fn build(self) -> Result<HttpClient, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .default_headers(self.default_headers)
+            .build()?;
+        Ok(HttpClient {
+            client,
+            retry: self.retry,
+        })
+    }
+}
+
+/// HTTP client retrying the requests failing with a transient error.
+///
+/// ```no_run
+/// # use color_eyre::{eyre::Report, Result};
+/// use bnacore::http::HttpClient;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Report> {
+/// let client = HttpClient::builder().build()?;
+/// let response = client
+///     .send(client.get("https://api.peopleforbikes.xyz/cities"))
+///     .await?
+///     .error_for_status()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl HttpClient {
+    /// Create a builder to configure a client.
+    pub fn builder() -> HttpClientBuilder {
+        HttpClientBuilder::default()
+    }
+
+    /// Return the retry policy of the client.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry
+    }
+
+    /// Start building a request.
+    pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    /// Start building a `GET` request.
+    pub fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    /// Start building a `POST` request.
+    pub fn post<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    /// Start building a `PUT` request.
+    pub fn put<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.put(url)
+    }
+
+    /// Start building a `PATCH` request.
+    pub fn patch<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.patch(url)
+    }
+
+    /// Start building a `DELETE` request.
+    pub fn delete<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.delete(url)
+    }
+
+    /// Send a request, retrying it on transient errors.
+    ///
+    /// Requests with a non-idempotent method are only retried on connection
+    /// errors. Once the retries are exhausted, the last response is returned,
+    /// even if it has an error status. Requests with a streaming body cannot be
+    /// retried and are sent only once.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, reqwest::Error> {
+        let idempotent = request
+            .try_clone()
+            .and_then(|r| r.build().ok())
+            .is_some_and(|r| is_idempotent_method(r.method()));
+        self.send_with_retries(request, idempotent).await
+    }
+
+    /// Send a request, retrying it on all transient errors, whatever its
+    /// method.
+    ///
+    /// Use it for the requests which can safely be repeated, like a `POST`
+    /// requesting an access token, or a `PATCH` replacing fields.
+    pub async fn send_idempotent(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<Response, reqwest::Error> {
+        self.send_with_retries(request, true).await
+    }
+
+    async fn send_with_retries(
+        &self,
+        request: RequestBuilder,
+        idempotent: bool,
+    ) -> Result<Response, reqwest::Error> {
+        let mut attempt = 0;
+        loop {
+            let retry = match request.try_clone() {
+                Some(retry) if attempt < self.retry.max_retries => retry,
+                _ => return request.send().await,
+            };
+            let delay = match retry.send().await {
+                Ok(response) if idempotent && is_retryable_status(response.status()) => {
+                    retry_after(&response).unwrap_or_else(|| self.retry.backoff(attempt))
+                }
+                Ok(response) => return Ok(response),
+                Err(err) if (idempotent || err.is_connect()) && is_retryable_error(&err) => {
+                    self.retry.backoff(attempt)
+                }
+                Err(err) => return Err(err),
+            };
+            tokio::time::sleep(delay.min(self.retry.max_delay)).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Return the process-wide HTTP client, with the default configuration.
+pub fn http_client() -> &'static HttpClient {
+    CLIENT.get_or_init(|| {
+        HttpClient::builder()
+            .build()
+            .expect("the default HTTP client configuration must be valid")
+    })
+}
+
+/// Return true if sending a request with this method several times has the
+/// same effect as sending it once.
+pub fn is_idempotent_method(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS | Method::TRACE
+    )
+}
+
+/// Return true if a response with this status should be retried.
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Return true if a request failing with this error should be retried.
+pub fn is_retryable_error(err: &reqwest::Error) -> bool {
+    err.is_connect() || err.is_timeout() || err.status().is_some_and(is_retryable_status)
+}
+
+/// Read the delay requested by the `Retry-After` header of a response.
+///
+/// The header can either contain a number of seconds, or an HTTP date.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    parse_retry_after(value, OffsetDateTime::now_utc())
+}
+
+/// Parse the value of a `Retry-After` header, relatively to `now`.
+fn parse_retry_after(value: &str, now: OffsetDateTime) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let format = format_description!(
+        "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+    );
+    let date = PrimitiveDateTime::parse(value.trim(), &format)
+        .ok()?
+        .assume_utc();
+    Some((date - now).try_into().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn client() -> HttpClient {
+        HttpClient::builder()
+            .retry(RetryPolicy {
+                max_retries: 2,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(10),
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        let delay = policy.backoff(2);
+        assert!(delay >= Duration::from_millis(400) && delay <= Duration::from_millis(800));
+        assert!(policy.backoff(30) <= policy.max_delay);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = datetime!(2015-10-21 07:28:00 UTC);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_server_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/cities"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/cities"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = client();
+        let response = client
+            .send(client.get(format!("{}/cities", server.uri())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_too_many_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = client();
+        let response = client
+            .send(
+                client
+                    .put(server.uri())
+                    .json(&serde_json::json!({"ci": "Austin"})),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_on_post() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = client();
+        let response = client
+            .send(
+                client
+                    .post(server.uri())
+                    .json(&serde_json::json!({"ci": "Austin"})),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_send_idempotent_retries_post() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = client();
+        let response = client
+            .send_idempotent(client.post(server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_retries_exhausted() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let client = client();
+        let response = client.send(client.get(server.uri())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_on_client_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = client();
+        let response = client.send(client.get(server.uri())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,529 @@
+//! Localize the rendered templates.
+//!
+//! Translations are stored in [Fluent](https://projectfluent.org/) catalogs,
+//! one `<locale>.ftl` file per locale. Once attached to a MiniJinja
+//! environment with [`Localization::register`], the templates can use:
+//!
+//! - the `t("key", name=value, ...)` function to translate a message,
+//! - the `number(decimals=0)` filter to format a number,
+//! - the `distance` filter to convert a distance in miles to the units of the
+//!   locale, and the `distance_unit()` function to display these units.
+//!
+//! The filters also accept numbers stored as strings, like the values read
+//! from a CSV file.
+//!
+//! The locale is read from the `locale` variable of the record being rendered,
+//! which is set by [`Localization::localize`].
+use crate::Error;
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use minijinja::{value::Kwargs, Environment, ErrorKind, State, Value};
+use serde::Serialize;
+use std::{collections::HashMap, ffi::OsStr, fs, path::Path, sync::Arc};
+use unic_langid::LanguageIdentifier;
+
+/// Name of the template variable holding the locale of a record.
+pub const LOCALE_VARIABLE: &str = "locale";
+
+/// Locale used when a translation is missing.
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+/// Number of kilometers in a mile.
+const KM_PER_MILE: f64 = 1.609344;
+
+/// Define the unit systems used to display distances.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Units {
+    /// Miles.
+    Imperial,
+    /// Kilometers.
+    Metric,
+}
+
+/// Describe the formatting conventions of a locale.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Conventions {
+    /// Unit system for the distances.
+    pub units: Units,
+    /// Decimal separator.
+    pub decimal_separator: char,
+    /// Digit group separator.
+    pub group_separator: char,
+}
+
+impl Conventions {
+    /// Regions still using miles for road distances.
+    const IMPERIAL_REGIONS: [&'static str; 4] = ["US", "GB", "LR", "MM"];
+    /// Languages using a comma as decimal separator.
+    const DECIMAL_COMMA_LANGUAGES: [&'static str; 13] = [
+        "de", "el", "es", "fa", "fr", "hr", "id", "it", "nl", "pl", "pt", "ru", "vi",
+    ];
+
+    /// Derive the conventions from a locale tag, like `en-US` or `es-ES`.
+    ///
+    /// ```
+    /// use bnacore::i18n::{Conventions, Units};
+    ///
+    /// let conventions = Conventions::from_locale("es-ES");
+    /// assert_eq!(conventions.units, Units::Metric);
+    /// assert_eq!(conventions.decimal_separator, ',');
+    /// assert_eq!(Conventions::from_locale("en-US").units, Units::Imperial);
+    /// ```
+    pub fn from_locale(locale: &str) -> Self {
+        let langid = locale.parse::<LanguageIdentifier>().unwrap_or_default();
+        let language = langid.language.as_str();
+        let region = langid.region.map(|r| r.as_str().to_string());
+        let units = match region {
+            Some(r) if Self::IMPERIAL_REGIONS.contains(&r.as_str()) => Units::Imperial,
+            Some(_) => Units::Metric,
+            None if language == "en" => Units::Imperial,
+            None => Units::Metric,
+        };
+        let (decimal_separator, group_separator) =
+            match Self::DECIMAL_COMMA_LANGUAGES.contains(&language) {
+                true if language == "fr" => (',', '\u{202f}'),
+                true => (',', '.'),
+                false => ('.', ','),
+            };
+        Self {
+            units,
+            decimal_separator,
+            group_separator,
+        }
+    }
+
+    /// Format a number with a fixed number of decimals.
+    ///
+    /// ```
+    /// use bnacore::i18n::Conventions;
+    ///
+    /// assert_eq!(Conventions::from_locale("en-US").format_number(1234567.891, 2), "1,234,567.89");
+    /// assert_eq!(Conventions::from_locale("de-DE").format_number(1234.5, 1), "1.234,5");
+    /// assert_eq!(Conventions::from_locale("en-US").format_number(-42.0, 0), "-42");
+    /// ```
+    pub fn format_number(&self, value: f64, decimals: usize) -> String {
+        let formatted = format!("{:.*}", decimals, value.abs());
+        let (integer, fraction) = match formatted.split_once('.') {
+            Some((i, f)) => (i, Some(f)),
+            None => (formatted.as_str(), None),
+        };
+
+        // Group the digits of the integer part by 3.
+        let mut grouped = String::with_capacity(integer.len() + integer.len() / 3);
+        for (i, c) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                grouped.push(self.group_separator);
+            }
+            grouped.push(c);
+        }
+
+        let mut number = String::new();
+        if value.is_sign_negative() && formatted.chars().any(|c| c != '0' && c != '.') {
+            number.push('-');
+        }
+        number.push_str(&grouped);
+        if let Some(fraction) = fraction {
+            number.push(self.decimal_separator);
+            number.push_str(fraction);
+        }
+        number
+    }
+
+    /// Convert a distance in miles to the units of the locale.
+    pub fn distance(&self, miles: f64) -> f64 {
+        match self.units {
+            Units::Imperial => miles,
+            Units::Metric => miles * KM_PER_MILE,
+        }
+    }
+}
+
+/// Return the locale used by default for a country.
+///
+/// Countries are matched case-insensitively. The countries of the United
+/// Kingdom are also supported.
+///
+/// ```
+/// use bnacore::i18n::locale_from_country;
+///
+/// assert_eq!(locale_from_country("Spain"), Some("es-ES"));
+/// assert_eq!(locale_from_country("united states"), Some("en-US"));
+/// assert_eq!(locale_from_country("Atlantis"), None);
+/// ```
+pub fn locale_from_country(country: &str) -> Option<&'static str> {
+    let locale = match country.trim().to_lowercase().as_str() {
+        "australia" => "en-AU",
+        "belgium" => "nl-BE",
+        "brazil" => "pt-BR",
+        "canada" => "en-CA",
+        "chile" => "es-CL",
+        "colombia" => "es-CO",
+        "croatia" => "hr-HR",
+        "cuba" => "es-CU",
+        "england" | "northern ireland" | "scotland" | "united kingdom" | "wales" => "en-GB",
+        "france" => "fr-FR",
+        "germany" => "de-DE",
+        "greece" => "el-GR",
+        "guatemala" => "es-GT",
+        "iran" => "fa-IR",
+        "iraq" => "ar-IQ",
+        "ireland" => "en-IE",
+        "italy" => "it-IT",
+        "mexico" => "es-MX",
+        "netherlands" => "nl-NL",
+        "new zealand" => "en-NZ",
+        "portugal" => "pt-PT",
+        "spain" => "es-ES",
+        "united states" | "usa" => "en-US",
+        "vietnam" => "vi-VN",
+        _ => return None,
+    };
+    Some(locale)
+}
+
+/// Store the translations for several locales.
+pub struct Catalog {
+    /// Locale used when a translation is missing.
+    default_locale: String,
+    /// Translation bundles, by locale.
+    bundles: HashMap<String, FluentBundle<FluentResource>>,
+}
+
+impl Catalog {
+    /// Create an empty catalog.
+    pub fn new(default_locale: &str) -> Self {
+        Self {
+            default_locale: default_locale.to_string(),
+            bundles: HashMap::new(),
+        }
+    }
+
+    /// Load all the `<locale>.ftl` files from a directory.
+    ///
+    /// The directory must contain at least one catalog.
+    pub fn from_dir(dir: &Path, default_locale: &str) -> Result<Self, Error> {
+        let mut catalog = Self::new(default_locale);
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension() != Some(OsStr::new("ftl")) {
+                continue;
+            }
+            if let Some(locale) = path.file_stem().and_then(OsStr::to_str) {
+                catalog.add_resource(locale, &fs::read_to_string(&path)?)?;
+            }
+        }
+        if catalog.bundles.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "no translation catalog found in {}",
+                dir.display()
+            )));
+        }
+        Ok(catalog)
+    }
+
+    /// Add Fluent messages for a specific locale.
+    pub fn add_resource(&mut self, locale: &str, source: &str) -> Result<(), Error> {
+        let langid = locale
+            .parse::<LanguageIdentifier>()
+            .map_err(|e| Error::InvalidArgument(format!("invalid locale `{locale}`: {e}")))?;
+        let resource = FluentResource::try_new(source.to_string()).map_err(|(_, errors)| {
+            Error::InvalidArgument(format!(
+                "invalid Fluent resource for `{locale}`: {errors:?}"
+            ))
+        })?;
+        let bundle = self.bundles.entry(locale.to_string()).or_insert_with(|| {
+            let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+            // The Unicode isolation marks would end up in the SVG documents.
+            bundle.set_use_isolating(false);
+            bundle
+        });
+        bundle.add_resource(resource).map_err(|errors| {
+            Error::InvalidArgument(format!(
+                "cannot add the Fluent resource for `{locale}`: {errors:?}"
+            ))
+        })
+    }
+
+    /// Return the locales available in the catalog.
+    pub fn locales(&self) -> Vec<String> {
+        let mut locales = self.bundles.keys().cloned().collect::<Vec<String>>();
+        locales.sort();
+        locales
+    }
+
+    /// Find the best bundle for a locale.
+    ///
+    /// The exact locale is preferred, then a locale with the same language,
+    /// then the default locale.
+    fn bundle(&self, locale: &str) -> Option<&FluentBundle<FluentResource>> {
+        if let Some(bundle) = self.bundles.get(locale) {
+            return Some(bundle);
+        }
+        let language = locale.split(['-', '_']).next().unwrap_or_default();
+        let mut locales = self.locales();
+        locales.retain(|l| l.split(['-', '_']).next() == Some(language));
+        locales
+            .first()
+            .and_then(|l| self.bundles.get(l))
+            .or_else(|| self.bundles.get(&self.default_locale))
+    }
+
+    /// Translate a message.
+    ///
+    /// The key is returned as-is if the message cannot be found.
+    ///
+    /// ```
+    /// use bnacore::i18n::Catalog;
+    ///
+    /// let mut catalog = Catalog::new("en-US");
+    /// catalog.add_resource("en-US", "miles = { $count } miles").unwrap();
+    /// catalog.add_resource("es", "miles = { $count } millas").unwrap();
+    /// let args = [("count", "12".into())];
+    /// assert_eq!(catalog.translate("es-MX", "miles", &args), "12 millas");
+    /// assert_eq!(catalog.translate("fr-FR", "miles", &args), "12 miles");
+    /// assert_eq!(catalog.translate("fr-FR", "unknown", &[]), "unknown");
+    /// ```
+    pub fn translate(&self, locale: &str, key: &str, args: &[(&str, FluentValue)]) -> String {
+        let Some(bundle) = self.bundle(locale) else {
+            return key.to_string();
+        };
+        let Some(pattern) = bundle.get_message(key).and_then(|m| m.value()) else {
+            return key.to_string();
+        };
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, value.clone());
+        }
+        let mut errors = vec![];
+        bundle
+            .format_pattern(pattern, Some(&fluent_args), &mut errors)
+            .to_string()
+    }
+}
+
+/// Describe how the locale of a record is selected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocaleSelection {
+    /// Use the same locale for all the records.
+    Fixed(String),
+    /// Derive the locale from the country stored in a record field, falling
+    /// back to the default locale of the catalog.
+    FromCountry(String),
+}
+
+/// Attach a translation catalog to the templates.
+#[derive(Clone)]
+pub struct Localization {
+    catalog: Arc<Catalog>,
+    selection: LocaleSelection,
+}
+
+impl Localization {
+    /// Create a new localization.
+    pub fn new(catalog: Catalog, selection: LocaleSelection) -> Self {
+        Self {
+            catalog: Arc::new(catalog),
+            selection,
+        }
+    }
+
+    /// Return the locale to use for a record.
+    pub fn locale_for(&self, record: &serde_json::Value) -> String {
+        match &self.selection {
+            LocaleSelection::Fixed(locale) => locale.clone(),
+            LocaleSelection::FromCountry(field) => record
+                .get(field)
+                .and_then(|c| c.as_str())
+                .and_then(locale_from_country)
+                .map_or_else(|| self.catalog.default_locale.clone(), String::from),
+        }
+    }
+
+    /// Add the locale of the record to the record itself, so the localization
+    /// functions can use it while rendering.
+    pub fn localize<S: Serialize>(&self, record: &S) -> Result<serde_json::Value, Error> {
+        let mut value = serde_json::to_value(record)?;
+        let locale = self.locale_for(&value);
+        if let serde_json::Value::Object(map) = &mut value {
+            map.insert(LOCALE_VARIABLE.to_string(), locale.into());
+        }
+        Ok(value)
+    }
+
+    /// Register the localization functions and filters into an environment.
+    pub fn register(&self, env: &mut Environment) {
+        let catalog = Arc::clone(&self.catalog);
+        env.add_function(
+            "t",
+            move |state: &State, key: &str, kwargs: Kwargs| -> Result<String, minijinja::Error> {
+                let locale = current_locale(state, &catalog);
+                let mut args: Vec<(&str, FluentValue)> = Vec::new();
+                for name in kwargs.args() {
+                    let value = kwargs.get::<Value>(name)?;
+                    let fluent_value = match f64::try_from(value.clone()) {
+                        Ok(n) if value.is_number() => FluentValue::from(n),
+                        _ => FluentValue::from(value.to_string()),
+                    };
+                    args.push((name, fluent_value));
+                }
+                Ok(catalog.translate(&locale, key, &args))
+            },
+        );
+
+        let catalog = Arc::clone(&self.catalog);
+        env.add_filter(
+            "number",
+            move |state: &State,
+                  value: Value,
+                  decimals: Option<usize>|
+                  -> Result<String, minijinja::Error> {
+                let locale = current_locale(state, &catalog);
+                Ok(Conventions::from_locale(&locale)
+                    .format_number(to_number(&value)?, decimals.unwrap_or(0)))
+            },
+        );
+
+        let catalog = Arc::clone(&self.catalog);
+        env.add_filter(
+            "distance",
+            move |state: &State, miles: Value| -> Result<f64, minijinja::Error> {
+                let locale = current_locale(state, &catalog);
+                Ok(Conventions::from_locale(&locale)
+                    .distance(to_number(&miles)?)
+                    .round())
+            },
+        );
+
+        let catalog = Arc::clone(&self.catalog);
+        env.add_function("distance_unit", move |state: &State| -> String {
+            let locale = current_locale(state, &catalog);
+            let key = match Conventions::from_locale(&locale).units {
+                Units::Imperial => "unit-miles",
+                Units::Metric => "unit-kilometers",
+            };
+            catalog.translate(&locale, key, &[])
+        });
+    }
+}
+
+/// Convert a template value to a number, parsing the strings.
+fn to_number(value: &Value) -> Result<f64, minijinja::Error> {
+    if value.is_number() {
+        if let Ok(n) = f64::try_from(value.clone()) {
+            return Ok(n);
+        }
+    }
+    value
+        .as_str()
+        .and_then(|s| s.trim().parse::<f64>().ok())
+        .ok_or_else(|| {
+            minijinja::Error::new(
+                ErrorKind::InvalidOperation,
+                format!("`{value}` is not a number"),
+            )
+        })
+}
+
+/// Return the locale of the record being rendered.
+fn current_locale(state: &State, catalog: &Catalog) -> String {
+    state
+        .lookup(LOCALE_VARIABLE)
+        .and_then(|l| l.as_str().map(String::from))
+        .unwrap_or_else(|| catalog.default_locale.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn localization(selection: LocaleSelection) -> Localization {
+        let mut catalog = Catalog::new("en-US");
+        catalog
+            .add_resource(
+                "en-US",
+                "low-stress = Low stress network\nunit-miles = miles\nunit-kilometers = km",
+            )
+            .unwrap();
+        catalog
+            .add_resource(
+                "es-ES",
+                "low-stress = Red de bajo estrés\nunit-miles = millas\nunit-kilometers = km",
+            )
+            .unwrap();
+        Localization::new(catalog, selection)
+    }
+
+    fn render(localization: &Localization, template: &str, record: serde_json::Value) -> String {
+        let mut env = Environment::new();
+        localization.register(&mut env);
+        env.add_template("test", template).unwrap();
+        let record = localization.localize(&record).unwrap();
+        env.get_template("test").unwrap().render(record).unwrap()
+    }
+
+    #[test]
+    fn test_locale_from_country_field() {
+        let l10n = localization(LocaleSelection::FromCountry("co".to_string()));
+        assert_eq!(l10n.locale_for(&json!({"co": "Spain"})), "es-ES");
+        assert_eq!(l10n.locale_for(&json!({"co": "Atlantis"})), "en-US");
+        assert_eq!(l10n.locale_for(&json!({"ci": "Madrid"})), "en-US");
+    }
+
+    #[test]
+    fn test_render_localized() {
+        let l10n = localization(LocaleSelection::FromCountry("co".to_string()));
+        let template = "{{ t('low-stress') }}: {{ lsm | distance | number }} {{ distance_unit() }}";
+        assert_eq!(
+            render(&l10n, template, json!({"co": "Spain", "lsm": 1000})),
+            "Red de bajo estrés: 1.609 km"
+        );
+        assert_eq!(
+            render(&l10n, template, json!({"co": "United States", "lsm": 1000})),
+            "Low stress network: 1,000 miles"
+        );
+    }
+
+    #[test]
+    fn test_render_string_numbers() {
+        let l10n = localization(LocaleSelection::Fixed("es-ES".to_string()));
+        assert_eq!(
+            render(
+                &l10n,
+                "{{ lsm | distance | number }} / {{ ra | number(1) }}",
+                json!({"lsm": "1000", "ra": " 47.26 "})
+            ),
+            "1.609 / 47,3"
+        );
+    }
+
+    #[test]
+    fn test_render_invalid_number() {
+        let l10n = localization(LocaleSelection::Fixed("es-ES".to_string()));
+        let mut env = Environment::new();
+        l10n.register(&mut env);
+        env.add_template("test", "{{ ci | number }}").unwrap();
+        let record = l10n.localize(&json!({"ci": "Madrid"})).unwrap();
+        assert!(env.get_template("test").unwrap().render(record).is_err());
+    }
+
+    #[test]
+    fn test_catalog_from_empty_dir() {
+        let dir =
+            std::env::temp_dir().join(format!("bnacore-empty-catalog-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let catalog = Catalog::from_dir(&dir, DEFAULT_LOCALE);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(catalog.is_err());
+    }
+
+    #[test]
+    fn test_render_fixed_locale() {
+        let l10n = localization(LocaleSelection::Fixed("es-ES".to_string()));
+        let rendered = render(
+            &l10n,
+            "{{ ra | number(1) }}",
+            json!({"co": "Canada", "ra": 47.26}),
+        );
+        assert_eq!(rendered, "47,3");
+    }
+}
@@ -1,10 +1,18 @@
 //! This crate defines the structures and functions which are shared between
 //! the PFB projects.
+pub mod auth;
 pub mod aws;
+pub mod bna_api;
+pub mod brochure;
 pub mod bundle;
 pub mod combine;
+pub mod database;
+pub mod http;
+pub mod i18n;
 pub mod neon;
 pub mod scorecard;
+pub mod serde_util;
+pub mod storage;
 pub mod template;
 pub mod versioning;
 
@@ -35,11 +43,11 @@ pub enum Error {
     Internal(String),
 
     /// Error from the URL crate.
-    #[error("URL error")]
+    #[error("URL error: {0}")]
     Url(#[from] url::ParseError),
 
     /// Error from the CSV crate.
-    #[error("CSV error")]
+    #[error("CSV error: {0}")]
     Csv(#[from] csv::Error),
 
     /// Line too long.
@@ -47,11 +55,11 @@ pub enum Error {
     InvalidArgument(String),
 
     /// I/O Error.
-    #[error("I/O error")]
+    #[error("I/O error: {0}")]
     IOError(#[from] io::Error),
 
     /// Error from the MiniJinja crate .
-    #[error("MiniJinja error")]
+    #[error("MiniJinja error: {}", error_chain(.0))]
     MiniJinja(#[from] minijinja::Error),
 
     /// Windows prompt too long.
@@ -59,24 +67,98 @@ pub enum Error {
     PromptTooLong,
 
     /// Error from the Zip crate.
-    #[error("Zip error")]
+    #[error("Zip error: {0}")]
     ZipError(#[from] zip::result::ZipError),
 
     /// Error from the Serde crate.
-    #[error("Serde JSON error")]
+    #[error("Serde JSON error: {0}")]
     SerdeJSON(#[from] serde_json::Error),
 
     /// Error From the Reqwest crate.
-    #[error("Reqwest error")]
+    #[error("HTTP error: {}", error_chain(.0))]
     Reqwest(#[from] reqwest::Error),
 
     /// AWS error from the bnacore::aws module.
-    #[error("AWS error")]
+    #[error("AWS error: {0}")]
     BNAAWS(#[from] aws::AWSError),
 
     /// Environment variable error.
-    #[error("Environment variable error")]
+    #[error("Environment variable error: {0}")]
     VarError(#[from] std::env::VarError),
+
+    /// Error from the bnacore::neon module.
+    #[error("Neon error: {0}")]
+    Neon(#[from] neon::NeonError),
+
+    /// Error from the tokio-postgres crate.
+    #[error("Postgres error: {}", error_chain(.0))]
+    Postgres(#[from] tokio_postgres::Error),
+
+    /// Error from the TOML crate.
+    #[error("TOML error: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    /// Several records produce the same output name.
+    #[error("Duplicate output name: {0}")]
+    DuplicateName(String),
+}
+
+impl Error {
+    /// Return true if the error comes from a missing resource, like a file, an
+    /// S3 object, a parameter or an HTTP `404 Not Found` response.
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Error::BNAAWS(e) => e.is_not_found(),
+            Error::IOError(e) => e.kind() == io::ErrorKind::NotFound,
+            Error::Reqwest(e) => e.status() == Some(reqwest::StatusCode::NOT_FOUND),
+            Error::Neon(e) => e.is_not_found(),
+            _ => false,
+        }
+    }
+
+    /// Return true if the error is transient, and the operation can be retried.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::BNAAWS(e) => e.is_retryable(),
+            Error::IOError(e) => matches!(
+                e.kind(),
+                io::ErrorKind::TimedOut
+                    | io::ErrorKind::Interrupted
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+            ),
+            Error::Reqwest(e) => http::is_retryable_error(e),
+            Error::Neon(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// Return true if the error comes from an exceeded quota, like the maximum
+    /// number of Neon branches.
+    pub fn is_limit_exceeded(&self) -> bool {
+        match self {
+            Error::Neon(e) => e.is_limit_exceeded(),
+            _ => false,
+        }
+    }
+}
+
+/// Format an error followed by its sources, separated by `: `.
+///
+/// The sources repeating the message of their parent are skipped, since some
+/// errors already include the message of their source.
+pub(crate) fn error_chain(err: &(dyn std::error::Error + 'static)) -> String {
+    let mut chain = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        let message = err.to_string();
+        if !chain.ends_with(&message) {
+            chain.push_str(": ");
+            chain.push_str(&message);
+        }
+        source = err.source();
+    }
+    chain
 }
 
 impl std::convert::From<Error> for PyErr {
@@ -283,6 +365,18 @@ pub fn word_chunks(words: &[String], limit: usize) -> Result<Vec<Vec<String>>, E
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_error_display_keeps_sources() {
+        let err = Error::from(io::Error::new(io::ErrorKind::NotFound, "scores.csv"));
+        assert_eq!(err.to_string(), "I/O error: scores.csv");
+        assert!(err.is_not_found());
+        assert!(!err.is_retryable());
+
+        let err = Error::from(aws::AWSError::SecretNotFound("BNA_API".to_string()));
+        assert_eq!(err.to_string(), "AWS error: secret `BNA_API` not found");
+        assert!(err.is_not_found());
+    }
+
     #[test]
     #[should_panic(
         expected = "The length of the word (\\\"gastropub\\\" (9)) exceeds the limit defined(5)."
@@ -1,13 +1,50 @@
+use super::ANALYSIS_BRANCH_PREFIX;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
+use std::{collections::HashSet, fmt};
+use thiserror::Error;
 use time::OffsetDateTime;
+use url::Url;
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct NeonError {
+/// An error returned by the Neon API.
+#[derive(Error, Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[error("Neon API error {status} {code}: {message}")]
+pub struct NeonApiError {
+    /// The HTTP status of the response.
+    #[serde(skip)]
+    pub status: u16,
+    /// The error code, like `BRANCHES_LIMIT_EXCEEDED`.
+    #[serde(default)]
     pub code: String,
+    /// The error message.
+    #[serde(default)]
     pub message: String,
 }
 
+impl NeonApiError {
+    /// Return true if a quota of the project, like its maximum number of
+    /// branches, is exceeded.
+    pub fn is_limit_exceeded(&self) -> bool {
+        self.code.to_ascii_uppercase().contains("LIMIT_EXCEEDED")
+            || self.message.to_ascii_lowercase().contains("limit exceeded")
+    }
+
+    /// Return true if the resource does not exist.
+    pub fn is_not_found(&self) -> bool {
+        self.status == 404
+    }
+
+    /// Return true if the project is locked by running operations.
+    pub fn is_locked(&self) -> bool {
+        self.status == 423
+    }
+
+    /// Return true if the request can be retried later.
+    pub fn is_retryable(&self) -> bool {
+        self.is_locked() || self.status == 429 || (500..600).contains(&self.status)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ListBranchResponse {
     #[serde(flatten)]
@@ -17,6 +54,31 @@ pub struct ListBranchResponse {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ListBranchResponses {
     pub branches: Vec<ListBranchResponse>,
+    #[serde(default)]
+    pub pagination: Option<Pagination>,
+}
+
+impl ListBranchResponses {
+    /// Return the branches created for the analyses.
+    ///
+    /// The branches which are the parents of other branches, like snapshots,
+    /// are not analysis branches, whatever their names.
+    pub fn analysis_branches(&self) -> impl Iterator<Item = &Branch> {
+        let parents = self
+            .branches
+            .iter()
+            .filter_map(|b| b.branch.parent_id.as_deref())
+            .collect::<HashSet<&str>>();
+        self.branches.iter().map(|b| &b.branch).filter(move |b| {
+            b.is_analysis() && !b.id.as_deref().is_some_and(|id| parents.contains(id))
+        })
+    }
+
+    /// Return true if another analysis branch can be created without
+    /// exceeding `max_branches` analysis branches.
+    pub fn has_capacity(&self, max_branches: usize) -> bool {
+        self.analysis_branches().count() < max_branches
+    }
 }
 
 /// The compute endpoint type. Either read_write or read_only.
@@ -109,7 +171,7 @@ pub struct Endpoint {
     /// automatically suspended. The value 0 means use the global default.
     /// The value -1 means never suspend. The default value is 300 seconds (5 minutes).
     /// The maximum value is 604800 seconds (1 week).
-    pub suspend_timeout_seconds: Option<u64>,
+    pub suspend_timeout_seconds: Option<i64>,
     /// The compute endpoint type.
     pub r#type: EndpointType,
     /// A timestamp indicating when the compute endpoint was last updated.
@@ -118,7 +180,7 @@ pub struct Endpoint {
     pub updated_at: Option<OffsetDateTime>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum BranchState {
     Init,
@@ -160,9 +222,13 @@ pub struct Branch {
     pub pending_state: Option<BranchState>,
     /// Whether the branch is the project's primary branch
     pub primary: Option<bool>,
+    /// Whether the branch is the project's default branch.
+    /// This field replaces `primary` in the newer versions of the API.
+    pub default: Option<bool>,
     /// A timestamp identifying a point in time on the parent branch.
     /// The branch will be created with data starting from this point in time.
-    #[serde(with = "time::serde::iso8601::option")]
+    // Serialized as RFC 3339, since the API rejects the six-digit years of ISO 8601.
+    #[serde(with = "time::serde::rfc3339::option")]
     #[serde(default)]
     pub timestamp: Option<OffsetDateTime>,
     /// A timestamp indicating when the branch was last updated.
@@ -172,6 +238,23 @@ pub struct Branch {
     pub written_data_bytes: Option<u64>,
 }
 
+impl Branch {
+    /// Return true if the branch is the project's primary branch.
+    pub fn is_primary(&self) -> bool {
+        self.primary.unwrap_or_default() || self.default.unwrap_or_default()
+    }
+
+    /// Return true if the branch is named like the branches created for the
+    /// analyses.
+    pub fn is_analysis(&self) -> bool {
+        !self.is_primary()
+            && self
+                .name
+                .as_deref()
+                .is_some_and(|n| n.starts_with(ANALYSIS_BRANCH_PREFIX))
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Action {
@@ -190,6 +273,38 @@ pub enum Action {
     TenantDetach,
     TenantIgnore,
     TenantReattach,
+    /// An action which is not modeled yet.
+    #[serde(other)]
+    Other,
+}
+
+/// The status of an operation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationStatus {
+    Scheduling,
+    Running,
+    Finished,
+    Failed,
+    Error,
+    Cancelling,
+    Cancelled,
+    Skipped,
+}
+
+impl OperationStatus {
+    /// Return true if the operation completed successfully.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, OperationStatus::Finished | OperationStatus::Skipped)
+    }
+
+    /// Return true if the operation will never complete successfully.
+    pub fn is_failed(&self) -> bool {
+        matches!(
+            self,
+            OperationStatus::Failed | OperationStatus::Error | OperationStatus::Cancelled
+        )
+    }
 }
 
 #[skip_serializing_none]
@@ -218,7 +333,7 @@ pub struct Operation {
     #[serde(default)]
     pub retry_at: Option<OffsetDateTime>,
     /// The status of the operation.
-    pub status: Option<String>,
+    pub status: Option<OperationStatus>,
     /// The total duration of the operation in milliseconds.
     pub total_duration_ms: Option<u32>,
     /// A timestamp indicating when the operation was last updated.
@@ -228,7 +343,7 @@ pub struct Operation {
 }
 
 #[skip_serializing_none]
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Deserialize, Serialize)]
 pub struct Role {
     /// The ID of the branch to which the role belongs.
     pub branch_id: Option<String>,
@@ -241,13 +356,26 @@ pub struct Role {
     /// The role password.
     pub password: Option<String>,
     /// Whether or not the role is system-protected.
-    pub role: Option<bool>,
+    pub protected: Option<bool>,
     /// A timestamp indicating when the role was last updated.
     #[serde(with = "time::serde::iso8601::option")]
     #[serde(default)]
     pub updated_at: Option<OffsetDateTime>,
 }
 
+impl fmt::Debug for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Role")
+            .field("branch_id", &self.branch_id)
+            .field("created_at", &self.created_at)
+            .field("name", &self.name)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("protected", &self.protected)
+            .field("updated_at", &self.updated_at)
+            .finish()
+    }
+}
+
 #[skip_serializing_none]
 #[derive(Default, Debug, Deserialize, Serialize)]
 pub struct Database {
@@ -282,6 +410,13 @@ pub struct CreateBranchResponse {
     pub operations: Vec<Operation>,
     pub roles: Vec<Role>,
     pub databases: Vec<Database>,
+    #[serde(default)]
+    pub connection_uris: Vec<ConnectionDetails>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BranchResponse {
+    pub branch: Branch,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -290,10 +425,192 @@ pub struct DeleteBranchResponse {
     pub operations: Vec<Operation>,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OperationResponse {
+    pub operation: Operation,
+}
+
+#[skip_serializing_none]
+#[derive(Default, Debug, Deserialize, Serialize)]
+pub struct Pagination {
+    /// The cursor of the last item of the page.
+    pub cursor: Option<String>,
+    /// The cursor to use to retrieve the next page.
+    pub next: Option<String>,
+}
+
+impl Pagination {
+    /// Return the cursor to use to retrieve the next page.
+    pub fn next_cursor(&self) -> Option<&str> {
+        self.next.as_deref().or(self.cursor.as_deref())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ListOperationsResponse {
+    pub operations: Vec<Operation>,
+    pub pagination: Option<Pagination>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ListEndpointsResponse {
+    pub endpoints: Vec<Endpoint>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EndpointResponse {
+    pub endpoint: Endpoint,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ListRolesResponse {
+    pub roles: Vec<Role>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RoleCreate {
+    /// The role name. Cannot exceed 63 bytes in length.
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CreateRoleRequest {
+    pub role: RoleCreate,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RoleResponse {
+    pub role: Role,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RolePasswordResponse {
+    /// The role password.
+    pub password: String,
+}
+
+impl fmt::Debug for RolePasswordResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RolePasswordResponse")
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ListDatabasesResponse {
+    pub databases: Vec<Database>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DatabaseCreate {
+    /// The database name.
+    pub name: String,
+    /// The name of role that owns the database.
+    pub owner_name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CreateDatabaseRequest {
+    pub database: DatabaseCreate,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DatabaseResponse {
+    pub database: Database,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ConnectionUriResponse {
+    /// The connection URI, including the role password.
+    pub uri: Url,
+}
+
+impl fmt::Debug for ConnectionUriResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionUriResponse")
+            .field("uri", &redact_uri(&self.uri))
+            .finish()
+    }
+}
+
+/// The connection URI and parameters of a database, returned when a branch
+/// is created.
+#[derive(Deserialize, Serialize)]
+pub struct ConnectionDetails {
+    /// The connection URI, including the role password.
+    pub connection_uri: Url,
+    pub connection_parameters: ConnectionParameters,
+}
+
+impl fmt::Debug for ConnectionDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionDetails")
+            .field("connection_uri", &redact_uri(&self.connection_uri))
+            .field("connection_parameters", &self.connection_parameters)
+            .finish()
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ConnectionParameters {
+    /// The database name.
+    pub database: String,
+    /// The role password.
+    pub password: String,
+    /// The role name.
+    pub role: String,
+    /// The hostname of the compute endpoint.
+    pub host: String,
+    /// The hostname of the connection pooler.
+    pub pooler_host: String,
+}
+
+impl fmt::Debug for ConnectionParameters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionParameters")
+            .field("database", &self.database)
+            .field("password", &"<redacted>")
+            .field("role", &self.role)
+            .field("host", &self.host)
+            .field("pooler_host", &self.pooler_host)
+            .finish()
+    }
+}
+
+/// Replace the password of a connection URI, to be able to log it.
+fn redact_uri(uri: &Url) -> String {
+    let mut redacted = uri.clone();
+    if redacted.password().is_some() {
+        let _ = redacted.set_password(Some("redacted"));
+    }
+    redacted.to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_deserialize_api_error() {
+        let raw_json = r#"
+        {
+          "code": "BRANCHES_LIMIT_EXCEEDED",
+          "message": "branches limit exceeded"
+        }"#;
+        let mut error = serde_json::from_str::<NeonApiError>(raw_json).unwrap();
+        error.status = 422;
+        assert!(error.is_limit_exceeded());
+        assert!(!error.is_retryable());
+        assert_eq!(
+            error.to_string(),
+            "Neon API error 422 BRANCHES_LIMIT_EXCEEDED: branches limit exceeded"
+        );
+    }
+
     #[test]
     fn test_deserialize_list_branch() {
         let raw_json = r#"
@@ -338,7 +655,7 @@ mod tests {
               "project_id": "patient-smoke-782429",
               "parent_id": "br-round-pine-192368",
               "parent_lsn": "0/35B7D70",
-              "name": "usa-santa-rosa-new-mexico",
+              "name": "analysis-usa-santa-rosa-new-mexico",
               "current_state": "ready",
               "logical_size": 39165952,
               "creation_source": "console",
@@ -354,7 +671,35 @@ mod tests {
           ]
         }"#;
         let v = serde_json::from_str::<ListBranchResponses>(raw_json).unwrap();
-        assert_eq!(v.branches.len(), 3)
+        assert_eq!(v.branches.len(), 3);
+        assert_eq!(v.analysis_branches().count(), 1);
+        assert!(v.has_capacity(2));
+        assert!(!v.has_capacity(1));
+    }
+
+    #[test]
+    fn test_analysis_branches_exclude_parents() {
+        let branch = |id: &str, name: &str, parent_id: Option<&str>| ListBranchResponse {
+            branch: Branch {
+                id: Some(id.to_string()),
+                name: Some(name.to_string()),
+                parent_id: parent_id.map(str::to_string),
+                ..Default::default()
+            },
+        };
+        let v = ListBranchResponses {
+            branches: vec![
+                branch("br-snapshot", "analysis-snapshot", None),
+                branch("br-child", "analysis-usa-austin-texas", Some("br-snapshot")),
+                branch("br-manual", "usa-provincetown-massachusetts", None),
+            ],
+            pagination: None,
+        };
+        let ids = v
+            .analysis_branches()
+            .filter_map(|b| b.id.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["br-child"]);
     }
 
     #[test]
@@ -481,6 +826,11 @@ mod tests {
         }
       "#;
         let deserialized = serde_json::from_str::<CreateBranchResponse>(raw_json).unwrap();
+        assert_eq!(
+            deserialized.connection_uris[0].connection_parameters.host,
+            "ep-super-unit-07200292.us-west-2.aws.neon.tech"
+        );
+        assert!(!format!("{deserialized:?}").contains("password@"));
         let _serialized = serde_json::to_string(&deserialized).unwrap();
     }
 
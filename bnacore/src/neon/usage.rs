@@ -0,0 +1,98 @@
+//! Estimate the cost of a branch from its consumption metrics.
+//!
+//! The metrics are reset at the beginning of each billing period, so the
+//! consumption of a branch must be collected before it is deleted.
+use super::model::Branch;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+/// Number of bytes in a GiB.
+const GIB: Decimal = dec!(1073741824);
+/// Number of seconds in an hour.
+const HOUR: Decimal = dec!(3600);
+
+/// Prices used to estimate the cost of a branch, in dollars.
+///
+/// The default prices can be overridden with the
+/// [`NeonSettings`](crate::database::NeonSettings).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NeonRates {
+    /// Price of a compute hour.
+    pub compute_hour: Decimal,
+    /// Price of a GiB of data written.
+    pub written_data_gib: Decimal,
+    /// Price of a GiB of data transferred out of Neon.
+    pub data_transfer_gib: Decimal,
+}
+
+impl Default for NeonRates {
+    fn default() -> Self {
+        Self {
+            compute_hour: dec!(0.16),
+            written_data_gib: dec!(0.096),
+            data_transfer_gib: dec!(0.09),
+        }
+    }
+}
+
+/// Consumption of a branch.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BranchUsage {
+    /// The branch ID.
+    pub branch_id: String,
+    /// Compute seconds used by the endpoints of the branch.
+    pub compute_time_seconds: u64,
+    /// Seconds during which the endpoints of the branch were active.
+    pub active_time_seconds: u64,
+    /// Amount of data written to the branch, in bytes.
+    pub written_data_bytes: u64,
+    /// Amount of data transferred out of the branch, in bytes.
+    pub data_transfer_bytes: u64,
+}
+
+impl BranchUsage {
+    /// Estimate the cost of the consumption, in dollars.
+    pub fn cost(&self, rates: &NeonRates) -> Decimal {
+        Decimal::from(self.compute_time_seconds) / HOUR * rates.compute_hour
+            + Decimal::from(self.written_data_bytes) / GIB * rates.written_data_gib
+            + Decimal::from(self.data_transfer_bytes) / GIB * rates.data_transfer_gib
+    }
+}
+
+impl From<&Branch> for BranchUsage {
+    fn from(branch: &Branch) -> Self {
+        Self {
+            branch_id: branch.id.clone().unwrap_or_default(),
+            compute_time_seconds: branch.compute_time_seconds.unwrap_or_default(),
+            active_time_seconds: branch.active_time_seconds.unwrap_or_default(),
+            written_data_bytes: branch.written_data_bytes.unwrap_or_default(),
+            data_transfer_bytes: branch.data_transfer_bytes.unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, 0, 0, dec!(0))]
+    #[case(3600, 0, 0, dec!(0.16))]
+    #[case(1800, 1073741824, 0, dec!(0.176))]
+    #[case(0, 0, 2147483648, dec!(0.18))]
+    fn test_cost(
+        #[case] compute_time_seconds: u64,
+        #[case] written_data_bytes: u64,
+        #[case] data_transfer_bytes: u64,
+        #[case] expected: Decimal,
+    ) {
+        let usage = BranchUsage {
+            compute_time_seconds,
+            written_data_bytes,
+            data_transfer_bytes,
+            ..Default::default()
+        };
+        assert_eq!(usage.cost(&NeonRates::default()), expected)
+    }
+}
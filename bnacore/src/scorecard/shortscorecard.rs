@@ -2,14 +2,14 @@ use super::{
     scorecard21::ScoreCard21, scorecard23::ScoreCard23, scorecard24::ScoreCard24, ScorecardCsv,
 };
 use pyo3::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 /// Represent a ScoreCard to be passed to `svggloo`.
 ///
 /// The fields must match all the fields from ScoreCard, and be represented by
 /// their short forms.
 #[pyclass]
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ShortScoreCard {
     /// City
     #[pyo3(get, set)]
@@ -0,0 +1,56 @@
+//! Inspect the types through their serde implementations.
+use serde::{
+    de::{self, Deserializer, Visitor},
+    Deserialize,
+};
+
+/// Return the names of the fields of a struct, as seen by serde.
+///
+/// The names account for the `rename` attributes. Flattened fields are not
+/// supported.
+///
+/// ```
+/// use bnacore::{scorecard::shortscorecard::ShortScoreCard, serde_util::struct_fields};
+///
+/// let fields = struct_fields::<ShortScoreCard>();
+/// assert!(fields.contains(&"rasc"));
+/// ```
+pub fn struct_fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldsCollector {
+        fields: &mut fields,
+    });
+    fields
+}
+
+/// Deserializer collecting the names of the fields of a struct.
+///
+/// It captures the field names when the struct asks to be deserialized, then
+/// aborts the deserialization.
+struct FieldsCollector<'a> {
+    fields: &'a mut &'static [&'static str],
+}
+
+impl<'de> Deserializer<'de> for FieldsCollector<'_> {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only structs are supported"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.fields = fields;
+        Err(de::Error::custom("fields collected"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
@@ -0,0 +1,257 @@
+//! Store objects in a local directory.
+use super::{file_etag, Storage};
+use crate::Error;
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use url::Url;
+use walkdir::WalkDir;
+
+/// Store objects in a local directory.
+///
+/// The keys are paths relative to the root directory. Directory keys, ending
+/// with a `/`, are stored as directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalStorage {
+    /// Root directory of the storage.
+    pub root: PathBuf,
+}
+
+impl LocalStorage {
+    /// Create a storage rooted in a directory.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Return the path of an object.
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key.trim_end_matches('/'))
+    }
+
+    /// Return the key of a path from the storage.
+    fn key(&self, path: &Path, is_dir: bool) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let mut key = relative
+            .components()
+            .map(|c| c.as_os_str().to_str())
+            .collect::<Option<Vec<&str>>>()?
+            .join("/");
+        if key.is_empty() {
+            return None;
+        }
+        if is_dir {
+            key.push('/');
+        }
+        Some(key)
+    }
+}
+
+impl Storage for LocalStorage {
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+        let mut keys: Vec<String> = Vec::new();
+        for entry in WalkDir::new(&self.root) {
+            let entry = entry.map_err(io::Error::from)?;
+            if let Some(key) = self.key(entry.path(), entry.file_type().is_dir()) {
+                if key.starts_with(prefix) {
+                    keys.push(key);
+                }
+            }
+        }
+        // Return the keys in the same order as S3.
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Error> {
+        Ok(fs::read(self.path(key))?)
+    }
+
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), Error> {
+        let path = self.path(key);
+        if key.ends_with('/') {
+            fs::create_dir_all(path)?;
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(fs::write(path, body)?)
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> Result<(), Error> {
+        let destination = self.path(key);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(path, destination)?;
+        Ok(())
+    }
+
+    async fn put_if_absent(&self, key: &str, body: Vec<u8>) -> Result<bool, Error> {
+        let path = self.path(key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let res = if key.ends_with('/') {
+            fs::create_dir(&path)
+        } else {
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(&body))
+        };
+        match res {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Compute the entity tag S3 would assign to the file.
+    async fn etag(&self, key: &str) -> Result<Option<String>, Error> {
+        let path = self.path(key);
+        if key.ends_with('/') || !path.is_file() {
+            return Ok(None);
+        }
+        file_etag(&path).map(Some)
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        let path = self.path(key);
+        Ok(if key.ends_with('/') {
+            path.is_dir()
+        } else {
+            path.is_file()
+        })
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        let path = self.path(key);
+        let res = if key.ends_with('/') {
+            fs::remove_dir(path)
+        } else {
+            fs::remove_file(path)
+        };
+        match res {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Return the `file://` URL of the object, which does not expire.
+    async fn presign(&self, key: &str, _expires_in: Duration) -> Result<String, Error> {
+        let path = fs::canonicalize(self.path(key))?;
+        Url::from_file_path(&path)
+            .map(String::from)
+            .map_err(|_| Error::InvalidArgument(format!("invalid path {}", path.display())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage(name: &str) -> LocalStorage {
+        let root = std::env::temp_dir().join(format!(
+            "bnacore-local-storage-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        LocalStorage::new(root)
+    }
+
+    #[tokio::test]
+    async fn test_put_get_delete() {
+        let storage = storage("put-get-delete");
+        storage
+            .put("usa/texas/austin/24.05/scores.csv", b"ci,ra\n".to_vec())
+            .await
+            .unwrap();
+        assert!(storage
+            .exists("usa/texas/austin/24.05/scores.csv")
+            .await
+            .unwrap());
+        assert_eq!(
+            storage
+                .get("usa/texas/austin/24.05/scores.csv")
+                .await
+                .unwrap(),
+            b"ci,ra\n"
+        );
+        storage
+            .delete("usa/texas/austin/24.05/scores.csv")
+            .await
+            .unwrap();
+        storage
+            .delete("usa/texas/austin/24.05/scores.csv")
+            .await
+            .unwrap();
+        assert!(!storage
+            .exists("usa/texas/austin/24.05/scores.csv")
+            .await
+            .unwrap());
+        fs::remove_dir_all(&storage.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_put_if_absent() {
+        let storage = storage("put-if-absent");
+        assert!(storage.put_if_absent("a/24.05/", vec![]).await.unwrap());
+        assert!(!storage.put_if_absent("a/24.05/", vec![]).await.unwrap());
+        assert!(storage
+            .put_if_absent("a/lock", b"1".to_vec())
+            .await
+            .unwrap());
+        assert!(!storage
+            .put_if_absent("a/lock", b"2".to_vec())
+            .await
+            .unwrap());
+        assert_eq!(storage.get("a/lock").await.unwrap(), b"1");
+        fs::remove_dir_all(&storage.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list() {
+        let storage = storage("list");
+        storage
+            .put("usa/texas/austin/24.05/", vec![])
+            .await
+            .unwrap();
+        storage
+            .put("usa/texas/austin/24.05.1/scores.csv", vec![])
+            .await
+            .unwrap();
+        storage.put("usa/texas/houston/", vec![]).await.unwrap();
+        let keys = storage.list("usa/texas/austin/").await.unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                "usa/texas/austin/",
+                "usa/texas/austin/24.05.1/",
+                "usa/texas/austin/24.05.1/scores.csv",
+                "usa/texas/austin/24.05/",
+            ]
+        );
+        assert!(storage.list("usa/nevada/").await.unwrap().is_empty());
+        fs::remove_dir_all(&storage.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_presign() {
+        let storage = storage("presign");
+        storage.put("scores.csv", vec![]).await.unwrap();
+        let url = storage.presign("scores.csv", Duration::ZERO).await.unwrap();
+        assert!(url.starts_with("file://"));
+        assert!(url.ends_with("/scores.csv"));
+        fs::remove_dir_all(&storage.root).unwrap();
+    }
+}
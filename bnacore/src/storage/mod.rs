@@ -0,0 +1,247 @@
+//! Store objects in S3 or in a local directory.
+//!
+//! The [`Storage`] trait abstracts an object storage with flat keys. "Directory"
+//! keys end with a `/` and represent empty marker objects, following the S3
+//! console convention.
+//!
+//! The [`StorageBackend`] enum selects the backend at runtime, which allows
+//! the code using the storage to run offline against a local directory, or
+//! against an S3-compatible server like MinIO.
+pub mod local;
+pub mod s3;
+pub mod upload;
+
+use crate::Error;
+use std::{
+    env,
+    fs::File,
+    future::Future,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub use self::{local::LocalStorage, s3::S3Storage};
+
+/// Environment variable selecting the local storage, by pointing to its root
+/// directory.
+pub const LOCAL_STORAGE_VARIABLE: &str = "BNA_LOCAL_STORAGE";
+
+/// Size above which the files are uploaded in several parts.
+pub const MULTIPART_THRESHOLD: u64 = 16 * 1024 * 1024;
+/// Size of the parts of a multipart upload.
+pub const PART_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Store objects.
+pub trait Storage {
+    /// List the keys starting with `prefix`.
+    fn list(&self, prefix: &str) -> impl Future<Output = Result<Vec<String>, Error>> + Send;
+
+    /// Retrieve the content of an object.
+    fn get(&self, key: &str) -> impl Future<Output = Result<Vec<u8>, Error>> + Send;
+
+    /// Store an object, replacing it if it exists.
+    fn put(&self, key: &str, body: Vec<u8>) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Store the content of a local file.
+    ///
+    /// Files larger than [`MULTIPART_THRESHOLD`] are uploaded in parts of
+    /// [`PART_SIZE`] bytes when the backend supports it.
+    fn put_file(&self, key: &str, path: &Path) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Store an object only if it does not exist yet.
+    ///
+    /// The check and the write are atomic. Returns `false` if the object
+    /// already exists, in which case it is left untouched.
+    fn put_if_absent(
+        &self,
+        key: &str,
+        body: Vec<u8>,
+    ) -> impl Future<Output = Result<bool, Error>> + Send;
+
+    /// Return the entity tag of an object, or `None` if it does not exist.
+    ///
+    /// The entity tag follows the S3 convention, and can be compared with the
+    /// result of [`file_etag`].
+    fn etag(&self, key: &str) -> impl Future<Output = Result<Option<String>, Error>> + Send;
+
+    /// Return true if an object exists.
+    fn exists(&self, key: &str) -> impl Future<Output = Result<bool, Error>> + Send;
+
+    /// Delete an object.
+    ///
+    /// Deleting an object which does not exist is not an error.
+    fn delete(&self, key: &str) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Return a URL granting a temporary read access to an object.
+    fn presign(
+        &self,
+        key: &str,
+        expires_in: Duration,
+    ) -> impl Future<Output = Result<String, Error>> + Send;
+}
+
+/// Select the storage backend at runtime.
+#[derive(Debug, Clone)]
+pub enum StorageBackend {
+    /// S3 bucket.
+    S3(S3Storage),
+    /// Local directory.
+    Local(LocalStorage),
+}
+
+impl StorageBackend {
+    /// Select the storage backend for a bucket from the environment.
+    ///
+    /// If the `BNA_LOCAL_STORAGE` variable is set, the bucket is a sub-directory
+    /// of the directory it points to. Otherwise the S3 bucket is used, with
+    /// the standard AWS configuration.
+    pub async fn from_env(bucket: &str) -> Self {
+        match env::var_os(LOCAL_STORAGE_VARIABLE) {
+            Some(root) => Self::Local(LocalStorage::new(PathBuf::from(root).join(bucket))),
+            None => Self::S3(S3Storage::from_env(bucket).await),
+        }
+    }
+}
+
+impl Storage for StorageBackend {
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        match self {
+            StorageBackend::S3(s) => s.list(prefix).await,
+            StorageBackend::Local(s) => s.list(prefix).await,
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Error> {
+        match self {
+            StorageBackend::S3(s) => s.get(key).await,
+            StorageBackend::Local(s) => s.get(key).await,
+        }
+    }
+
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), Error> {
+        match self {
+            StorageBackend::S3(s) => s.put(key, body).await,
+            StorageBackend::Local(s) => s.put(key, body).await,
+        }
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> Result<(), Error> {
+        match self {
+            StorageBackend::S3(s) => s.put_file(key, path).await,
+            StorageBackend::Local(s) => s.put_file(key, path).await,
+        }
+    }
+
+    async fn put_if_absent(&self, key: &str, body: Vec<u8>) -> Result<bool, Error> {
+        match self {
+            StorageBackend::S3(s) => s.put_if_absent(key, body).await,
+            StorageBackend::Local(s) => s.put_if_absent(key, body).await,
+        }
+    }
+
+    async fn etag(&self, key: &str) -> Result<Option<String>, Error> {
+        match self {
+            StorageBackend::S3(s) => s.etag(key).await,
+            StorageBackend::Local(s) => s.etag(key).await,
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        match self {
+            StorageBackend::S3(s) => s.exists(key).await,
+            StorageBackend::Local(s) => s.exists(key).await,
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        match self {
+            StorageBackend::S3(s) => s.delete(key).await,
+            StorageBackend::Local(s) => s.delete(key).await,
+        }
+    }
+
+    async fn presign(&self, key: &str, expires_in: Duration) -> Result<String, Error> {
+        match self {
+            StorageBackend::S3(s) => s.presign(key, expires_in).await,
+            StorageBackend::Local(s) => s.presign(key, expires_in).await,
+        }
+    }
+}
+
+/// Return the content type of an object, guessed from the extension of its key.
+pub fn content_type(key: &str) -> String {
+    mime_guess::from_path(key)
+        .first_or_octet_stream()
+        .essence_str()
+        .to_string()
+}
+
+/// Compute the entity tag S3 assigns to a file once uploaded.
+///
+/// The entity tag is the MD5 digest of the content for the files uploaded in
+/// a single request. For the multipart uploads, it is the MD5 digest of the
+/// concatenated digests of the parts, followed by `-` and the number of parts.
+pub fn file_etag(path: &Path) -> Result<String, Error> {
+    Ok(etag_with(path, MULTIPART_THRESHOLD, PART_SIZE)?)
+}
+
+/// Compute the entity tag of a file, for a specific multipart configuration.
+fn etag_with(path: &Path, threshold: u64, part_size: u64) -> io::Result<String> {
+    let size = path.metadata()?.len();
+    let mut file = File::open(path)?;
+    if size <= threshold {
+        let mut buffer = Vec::with_capacity(size as usize);
+        file.read_to_end(&mut buffer)?;
+        return Ok(format!("{:x}", md5::compute(&buffer)));
+    }
+
+    let mut digests: Vec<u8> = Vec::new();
+    let mut parts = 0;
+    loop {
+        let mut buffer = Vec::with_capacity(part_size as usize);
+        let read = file.by_ref().take(part_size).read_to_end(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        digests.extend_from_slice(&md5::compute(&buffer).0);
+        parts += 1;
+    }
+    Ok(format!("{:x}-{parts}", md5::compute(&digests)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::fs;
+
+    #[rstest]
+    #[case("usa/texas/austin.pdf", "application/pdf")]
+    #[case("output/austin.svg", "image/svg+xml")]
+    #[case("bundles/all.zip", "application/zip")]
+    #[case("24.05/scores.csv", "text/csv")]
+    #[case("24.05/", "application/octet-stream")]
+    fn test_content_type(#[case] key: &str, #[case] expected: &str) {
+        assert_eq!(content_type(key), expected)
+    }
+
+    #[test]
+    fn test_etag() {
+        let path = env::temp_dir().join(format!("bnacore-etag-{}", std::process::id()));
+        fs::write(&path, b"0123456789").unwrap();
+        assert_eq!(
+            etag_with(&path, 16, 4).unwrap(),
+            format!("{:x}", md5::compute(b"0123456789"))
+        );
+        let digests = [b"0123".as_slice(), b"4567", b"89"]
+            .iter()
+            .flat_map(|part| md5::compute(part).0)
+            .collect::<Vec<u8>>();
+        assert_eq!(
+            etag_with(&path, 8, 4).unwrap(),
+            format!("{:x}-3", md5::compute(digests))
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
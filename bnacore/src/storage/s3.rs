@@ -0,0 +1,307 @@
+//! Store objects in an S3 bucket.
+use super::{content_type, Storage, MULTIPART_THRESHOLD, PART_SIZE};
+use crate::{
+    aws::{AWSError, BoxError},
+    Error,
+};
+use aws_config::BehaviorVersion;
+use aws_sdk_s3::{
+    error::SdkError,
+    presigning::PresigningConfig,
+    primitives::{ByteStream, Length},
+    types::{CompletedMultipartUpload, CompletedPart},
+};
+use std::{path::Path, time::Duration};
+
+/// Store objects in an S3 bucket.
+#[derive(Debug, Clone)]
+pub struct S3Storage {
+    /// S3 client.
+    pub client: aws_sdk_s3::Client,
+    /// Name of the bucket.
+    pub bucket: String,
+}
+
+impl S3Storage {
+    /// Create a storage for a bucket.
+    pub fn new(client: aws_sdk_s3::Client, bucket: &str) -> Self {
+        Self {
+            client,
+            bucket: bucket.to_string(),
+        }
+    }
+
+    /// Create a storage for a bucket, with the standard AWS configuration.
+    ///
+    /// When a custom endpoint is configured, for instance with
+    /// `AWS_ENDPOINT_URL` to use MinIO, the requests use path-style addressing.
+    pub async fn from_env(bucket: &str) -> Self {
+        let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+        let s3_config = aws_sdk_s3::config::Builder::from(&config)
+            .force_path_style(config.endpoint_url().is_some())
+            .build();
+        Self::new(aws_sdk_s3::Client::from_conf(s3_config), bucket)
+    }
+
+    /// Convert a failed S3 call to a bnacore error.
+    fn error<E>(&self, operation: &'static str, key: &str, err: SdkError<E>) -> Error
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        Error::BNAAWS(AWSError::s3(operation, &self.bucket, key, err))
+    }
+
+    /// Convert an error happening around an S3 call to a bnacore error.
+    fn other_error<E: Into<BoxError>>(&self, operation: &'static str, key: &str, err: E) -> Error {
+        Error::BNAAWS(AWSError::S3 {
+            operation,
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            status: None,
+            transient: false,
+            source: err.into(),
+        })
+    }
+
+    /// Upload a file in parts of [`PART_SIZE`] bytes.
+    ///
+    /// The multipart upload is aborted if one of the parts fails, to avoid
+    /// being billed for the parts already stored.
+    async fn put_multipart(&self, key: &str, path: &Path, size: u64) -> Result<(), Error> {
+        let upload = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type(key))
+            .send()
+            .await
+            .map_err(|e| self.error("CreateMultipartUpload", key, e))?;
+        let upload_id = upload.upload_id().ok_or_else(|| {
+            self.other_error("CreateMultipartUpload", key, "no upload ID returned")
+        })?;
+
+        let res = self.upload_parts(key, path, size, upload_id).await;
+        if res.is_err() {
+            let _ = self
+                .client
+                .abort_multipart_upload()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .send()
+                .await;
+        }
+        res
+    }
+
+    /// Upload the parts of a file, then complete the multipart upload.
+    async fn upload_parts(
+        &self,
+        key: &str,
+        path: &Path,
+        size: u64,
+        upload_id: &str,
+    ) -> Result<(), Error> {
+        let mut parts: Vec<CompletedPart> = Vec::new();
+        let mut offset = 0;
+        let mut part_number = 1;
+        while offset < size {
+            let length = PART_SIZE.min(size - offset);
+            let body = ByteStream::read_from()
+                .path(path)
+                .offset(offset)
+                .length(Length::Exact(length))
+                .build()
+                .await
+                .map_err(|e| self.other_error("UploadPart", key, e))?;
+            let part = self
+                .client
+                .upload_part()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| self.error("UploadPart", key, e))?;
+            parts.push(
+                CompletedPart::builder()
+                    .set_e_tag(part.e_tag().map(str::to_string))
+                    .part_number(part_number)
+                    .build(),
+            );
+            offset += length;
+            part_number += 1;
+        }
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(parts))
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| self.error("CompleteMultipartUpload", key, e))?;
+        Ok(())
+    }
+}
+
+impl Storage for S3Storage {
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        let mut pages = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(prefix)
+            .into_paginator()
+            .send();
+        let mut keys: Vec<String> = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(|e| self.error("ListObjectsV2", prefix, e))?;
+            keys.extend(
+                page.contents()
+                    .iter()
+                    .filter_map(|o| o.key().map(str::to_string)),
+            );
+        }
+        Ok(keys)
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Error> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| self.error("GetObject", key, e))?;
+        let body = object
+            .body
+            .collect()
+            .await
+            .map_err(|e| self.other_error("GetObject", key, e))?;
+        Ok(body.into_bytes().to_vec())
+    }
+
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), Error> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type(key))
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(|e| self.error("PutObject", key, e))?;
+        Ok(())
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> Result<(), Error> {
+        let size = path.metadata()?.len();
+        if size > MULTIPART_THRESHOLD {
+            return self.put_multipart(key, path, size).await;
+        }
+        let body = ByteStream::from_path(path)
+            .await
+            .map_err(|e| self.other_error("PutObject", key, e))?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type(key))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| self.error("PutObject", key, e))?;
+        Ok(())
+    }
+
+    /// Store an object with a conditional `If-None-Match: *` request.
+    ///
+    /// S3 answers `412 Precondition Failed` if the object exists, or
+    /// `409 Conflict` if a concurrent conditional write is in progress.
+    async fn put_if_absent(&self, key: &str, body: Vec<u8>) -> Result<bool, Error> {
+        let res = self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .if_none_match("*")
+            .body(ByteStream::from(body))
+            .send()
+            .await;
+        match res {
+            Ok(_) => Ok(true),
+            Err(err)
+                if err
+                    .raw_response()
+                    .is_some_and(|r| matches!(r.status().as_u16(), 409 | 412)) =>
+            {
+                Ok(false)
+            }
+            Err(err) => Err(self.error("PutObject", key, err)),
+        }
+    }
+
+    async fn etag(&self, key: &str) -> Result<Option<String>, Error> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(object) => Ok(object.e_tag().map(|e| e.trim_matches('"').to_string())),
+            Err(err) if err.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(None),
+            Err(err) => Err(self.error("HeadObject", key, err)),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(err) if err.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(false),
+            Err(err) => Err(self.error("HeadObject", key, err)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| self.error("DeleteObject", key, e))?;
+        Ok(())
+    }
+
+    async fn presign(&self, key: &str, expires_in: Duration) -> Result<String, Error> {
+        let config = PresigningConfig::expires_in(expires_in)
+            .map_err(|e| self.other_error("GetObject", key, e))?;
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .presigned(config)
+            .await
+            .map_err(|e| self.error("GetObject", key, e))?;
+        Ok(request.uri().to_string())
+    }
+}
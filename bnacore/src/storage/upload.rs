@@ -0,0 +1,301 @@
+//! Upload local files to a storage.
+//!
+//! The files whose content did not change since the last upload are skipped,
+//! by comparing their entity tag with the one of the stored object.
+use super::{content_type, file_etag, Storage, StorageBackend};
+use crate::Error;
+use std::{
+    fmt::Display,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+use walkdir::WalkDir;
+
+/// Represent an `s3://bucket/prefix` destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadTarget {
+    /// Name of the bucket.
+    pub bucket: String,
+    /// Prefix of the keys, without leading or trailing `/`.
+    pub prefix: String,
+}
+
+impl UploadTarget {
+    /// Return the key of a file, relative to the prefix.
+    pub fn key(&self, relative: &str) -> String {
+        if self.prefix.is_empty() {
+            relative.to_string()
+        } else {
+            format!("{}/{relative}", self.prefix)
+        }
+    }
+
+    /// Return the storage of the bucket, selected from the environment.
+    pub async fn storage(&self) -> StorageBackend {
+        StorageBackend::from_env(&self.bucket).await
+    }
+}
+
+impl FromStr for UploadTarget {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let location = s
+            .strip_prefix("s3://")
+            .ok_or_else(|| Error::InvalidArgument(format!("`{s}` must start with `s3://`")))?;
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        if bucket.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "`{s}` must contain a bucket name"
+            )));
+        }
+        Ok(Self {
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+        })
+    }
+}
+
+impl Display for UploadTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.prefix.is_empty() {
+            write!(f, "s3://{}", self.bucket)
+        } else {
+            write!(f, "s3://{}/{}", self.bucket, self.prefix)
+        }
+    }
+}
+
+/// Status of an uploaded file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UploadStatus {
+    /// The file was uploaded.
+    Uploaded,
+    /// The file would have been uploaded, but this is a dry run.
+    Pending,
+    /// The stored object has the same content as the file.
+    Unchanged,
+}
+
+impl Display for UploadStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            UploadStatus::Uploaded => "uploaded",
+            UploadStatus::Pending => "pending",
+            UploadStatus::Unchanged => "unchanged",
+        };
+        write!(f, "{status}")
+    }
+}
+
+/// Represent a file to upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadEntry {
+    /// Path of the local file.
+    pub path: PathBuf,
+    /// Key of the object.
+    pub key: String,
+    /// Content type of the object.
+    pub content_type: String,
+    /// Size of the file, in bytes.
+    pub size: u64,
+    /// Status of the upload.
+    pub status: UploadStatus,
+}
+
+impl Display for UploadEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:<9} {} -> {} ({}, {} bytes)",
+            self.status,
+            self.path.display(),
+            self.key,
+            self.content_type,
+            self.size
+        )
+    }
+}
+
+/// Upload local files under a prefix of a storage.
+///
+/// ```no_run
+/// # use color_eyre::{eyre::Report, Result};
+/// use bnacore::storage::upload::{UploadTarget, Uploader};
+/// use std::path::Path;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Report> {
+/// let target: UploadTarget = "s3://brokenspoke-analyzer/brochures".parse()?;
+/// let storage = target.storage().await;
+/// let uploader = Uploader {
+///     storage: &storage,
+///     target: &target,
+///     dry_run: true,
+/// };
+/// for entry in uploader.upload_dir(Path::new("output")).await? {
+///     println!("{entry}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Uploader<'a, S> {
+    /// Storage to upload the files to.
+    pub storage: &'a S,
+    /// Destination of the files.
+    pub target: &'a UploadTarget,
+    /// List the files which would be uploaded, without uploading them.
+    pub dry_run: bool,
+}
+
+impl<S: Storage> Uploader<'_, S> {
+    /// Upload all the files of a directory, recursively.
+    ///
+    /// The keys are the paths of the files relative to the directory.
+    pub async fn upload_dir(&self, dir: &Path) -> Result<Vec<UploadEntry>, Error> {
+        let mut files: Vec<PathBuf> = Vec::new();
+        for entry in WalkDir::new(dir).sort_by_file_name() {
+            let entry = entry.map_err(std::io::Error::from)?;
+            if entry.file_type().is_file() {
+                files.push(entry.into_path());
+            }
+        }
+        self.upload_files(dir, &files).await
+    }
+
+    /// Upload files.
+    ///
+    /// The keys are the paths of the files relative to `base`, or their file
+    /// names if they are not located under `base`.
+    pub async fn upload_files(
+        &self,
+        base: &Path,
+        files: &[PathBuf],
+    ) -> Result<Vec<UploadEntry>, Error> {
+        let mut entries: Vec<UploadEntry> = Vec::with_capacity(files.len());
+        for path in files {
+            let relative = relative_key(base, path)?;
+            entries.push(self.upload_file(path, &self.target.key(&relative)).await?);
+        }
+        Ok(entries)
+    }
+
+    /// Upload a file, unless the stored object has the same content.
+    pub async fn upload_file(&self, path: &Path, key: &str) -> Result<UploadEntry, Error> {
+        let size = path.metadata()?.len();
+        let etag = file_etag(path)?;
+        let status = if self.storage.etag(key).await?.as_deref() == Some(etag.as_str()) {
+            UploadStatus::Unchanged
+        } else if self.dry_run {
+            UploadStatus::Pending
+        } else {
+            self.storage.put_file(key, path).await?;
+            UploadStatus::Uploaded
+        };
+        Ok(UploadEntry {
+            path: path.to_path_buf(),
+            key: key.to_string(),
+            content_type: content_type(key),
+            size,
+            status,
+        })
+    }
+}
+
+/// Return the key of a file relative to a base directory.
+fn relative_key(base: &Path, path: &Path) -> Result<String, Error> {
+    let relative = path
+        .strip_prefix(base)
+        .ok()
+        .filter(|r| r.components().all(|c| matches!(c, Component::Normal(_))))
+        .or_else(|| path.file_name().map(Path::new))
+        .ok_or_else(|| Error::InvalidArgument(format!("invalid file {}", path.display())))?;
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_str())
+        .collect::<Option<Vec<&str>>>()
+        .map(|parts| parts.join("/"))
+        .ok_or_else(|| Error::InvalidArgument(format!("invalid file {}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorage;
+    use rstest::rstest;
+    use std::fs;
+
+    #[rstest]
+    #[case("s3://bna/brochures/2024", "bna", "brochures/2024")]
+    #[case("s3://bna/brochures/", "bna", "brochures")]
+    #[case("s3://bna", "bna", "")]
+    fn test_upload_target(#[case] s: &str, #[case] bucket: &str, #[case] prefix: &str) {
+        let target = s.parse::<UploadTarget>().unwrap();
+        assert_eq!(target.bucket, bucket);
+        assert_eq!(target.prefix, prefix);
+    }
+
+    #[rstest]
+    #[case("bna/brochures")]
+    #[case("s3:///brochures")]
+    fn test_upload_target_invalid(#[case] s: &str) {
+        assert!(s.parse::<UploadTarget>().is_err())
+    }
+
+    #[rstest]
+    #[case("output", "output/usa/austin.pdf", "usa/austin.pdf")]
+    #[case("", "austin.pdf", "austin.pdf")]
+    #[case("", "../austin.pdf", "austin.pdf")]
+    #[case("output", "/tmp/austin.pdf", "austin.pdf")]
+    fn test_relative_key(#[case] base: &str, #[case] path: &str, #[case] expected: &str) {
+        assert_eq!(
+            relative_key(Path::new(base), Path::new(path)).unwrap(),
+            expected
+        )
+    }
+
+    #[tokio::test]
+    async fn test_upload_dir() {
+        let root = std::env::temp_dir().join(format!("bnacore-upload-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let output = root.join("output");
+        fs::create_dir_all(output.join("usa")).unwrap();
+        fs::write(output.join("usa/austin.svg"), b"<svg/>").unwrap();
+        fs::write(output.join("brussels.svg"), b"<svg/>").unwrap();
+        let storage = LocalStorage::new(root.join("bucket"));
+        let target: UploadTarget = "s3://bucket/brochures".parse().unwrap();
+        let mut uploader = Uploader {
+            storage: &storage,
+            target: &target,
+            dry_run: true,
+        };
+
+        let entries = uploader.upload_dir(&output).await.unwrap();
+        let keys = entries.iter().map(|e| e.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec!["brochures/brussels.svg", "brochures/usa/austin.svg"]
+        );
+        assert!(entries.iter().all(|e| e.status == UploadStatus::Pending));
+        assert_eq!(entries[0].content_type, "image/svg+xml");
+        assert!(!storage.exists("brochures/brussels.svg").await.unwrap());
+
+        uploader.dry_run = false;
+        let entries = uploader.upload_dir(&output).await.unwrap();
+        assert!(entries.iter().all(|e| e.status == UploadStatus::Uploaded));
+
+        fs::write(output.join("brussels.svg"), b"<svg></svg>").unwrap();
+        let entries = uploader.upload_dir(&output).await.unwrap();
+        let statuses = entries.iter().map(|e| e.status).collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            vec![UploadStatus::Uploaded, UploadStatus::Unchanged]
+        );
+        assert_eq!(
+            storage.get("brochures/brussels.svg").await.unwrap(),
+            b"<svg></svg>"
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,15 +1,26 @@
-use crate::Error;
+use crate::{
+    i18n::{Catalog, LocaleSelection, Localization, DEFAULT_LOCALE, LOCALE_VARIABLE},
+    Error,
+};
 use csv::Reader;
-use minijinja::Environment;
+use minijinja::{Environment, UndefinedBehavior};
 use serde::Serialize;
+use serde_json::{Map, Value};
 use std::{
-    collections::HashMap,
-    fs,
+    collections::{BTreeSet, HashSet},
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read},
     path::{Path, PathBuf},
     process::Command,
 };
 
-type Record = HashMap<String, String>;
+/// Represent a single data record used to render a template.
+///
+/// Values keep the type they had in the data source, therefore numbers from a
+/// JSON file can still be compared or formatted as numbers in the templates.
+/// The fields keep the order they had in the data source as well.
+pub type Record = Map<String, Value>;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Exporter {
@@ -18,54 +29,252 @@ pub enum Exporter {
     SVG2PDF,
 }
 
+/// Define the formats supported by the template data sources.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DataFormat {
+    /// CSV file with headers. All the values are read as strings.
+    Csv,
+    /// JSON array of objects.
+    Json,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+impl DataFormat {
+    /// Guess the data format from a file extension.
+    ///
+    /// ```
+    /// use bnacore::template::DataFormat;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(DataFormat::from_path(Path::new("scorecard.csv")), Some(DataFormat::Csv));
+    /// assert_eq!(DataFormat::from_path(Path::new("scorecard.ndjson")), Some(DataFormat::JsonLines));
+    /// assert_eq!(DataFormat::from_path(Path::new("scorecard.svg")), None);
+    /// ```
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension().and_then(OsStr::to_str)?;
+        match extension.to_lowercase().as_str() {
+            "csv" => Some(DataFormat::Csv),
+            "json" => Some(DataFormat::Json),
+            "jsonl" | "ndjson" => Some(DataFormat::JsonLines),
+            _ => None,
+        }
+    }
+}
+
+/// Describe where the data used to render a template comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataSource {
+    /// CSV file.
+    Csv(PathBuf),
+    /// JSON file containing an array of objects.
+    Json(PathBuf),
+    /// JSON Lines file.
+    JsonLines(PathBuf),
+    /// Standard input, in the specified format.
+    Stdin(DataFormat),
+    /// In-memory records.
+    Records(Vec<Record>),
+}
+
+impl DataSource {
+    /// Create a data source from a file path, using its extension to detect the
+    /// format.
+    ///
+    /// The special path `-` represents the standard input, and must be used
+    /// with [`DataSource::Stdin`] directly since the format cannot be guessed.
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        match DataFormat::from_path(path) {
+            Some(DataFormat::Csv) => Ok(DataSource::Csv(path.to_path_buf())),
+            Some(DataFormat::Json) => Ok(DataSource::Json(path.to_path_buf())),
+            Some(DataFormat::JsonLines) => Ok(DataSource::JsonLines(path.to_path_buf())),
+            None => Err(Error::InvalidArgument(format!(
+                "cannot detect the data format of {}",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Create a data source from any collection of serializable items.
+    ///
+    /// Each item must serialize to a map (a struct or a map for instance).
+    ///
+    /// ```
+    /// use bnacore::template::DataSource;
+    /// use std::collections::HashMap;
+    ///
+    /// let source = DataSource::from_records([HashMap::from([("city", "Austin")])]).unwrap();
+    /// let records = source.records().unwrap();
+    /// assert_eq!(records[0]["city"], "Austin");
+    /// ```
+    pub fn from_records<I, S>(records: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: Serialize,
+    {
+        let records = records
+            .into_iter()
+            .map(|r| to_record(serde_json::to_value(r)?))
+            .collect::<Result<Vec<Record>, Error>>()?;
+        Ok(DataSource::Records(records))
+    }
+
+    /// Load all the records from the data source.
+    pub fn records(&self) -> Result<Vec<Record>, Error> {
+        match self {
+            DataSource::Csv(path) => read_records(File::open(path)?, DataFormat::Csv),
+            DataSource::Json(path) => read_records(File::open(path)?, DataFormat::Json),
+            DataSource::JsonLines(path) => read_records(File::open(path)?, DataFormat::JsonLines),
+            DataSource::Stdin(format) => read_records(io::stdin().lock(), *format),
+            DataSource::Records(records) => Ok(records.clone()),
+        }
+    }
+}
+
+/// Read records in a specific format.
+pub fn read_records<R: Read>(reader: R, format: DataFormat) -> Result<Vec<Record>, Error> {
+    match format {
+        DataFormat::Csv => {
+            let mut csv_reader = Reader::from_reader(reader);
+            let headers = csv_reader.headers()?.clone();
+            let mut records = Vec::new();
+            for result in csv_reader.records() {
+                let record = result?;
+                records.push(
+                    headers
+                        .iter()
+                        .zip(record.iter())
+                        .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+                        .collect::<Record>(),
+                );
+            }
+            Ok(records)
+        }
+        DataFormat::Json => match serde_json::from_reader::<_, Value>(reader)? {
+            Value::Array(values) => values.into_iter().map(to_record).collect(),
+            _ => Err(Error::InvalidArgument(
+                "the JSON data must be an array of objects".to_string(),
+            )),
+        },
+        DataFormat::JsonLines => {
+            let mut records = Vec::new();
+            for line in BufReader::new(reader).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                records.push(to_record(serde_json::from_str::<Value>(&line)?)?);
+            }
+            Ok(records)
+        }
+    }
+}
+
+/// Convert a JSON value to a record.
+fn to_record(value: Value) -> Result<Record, Error> {
+    match value {
+        Value::Object(map) => Ok(map),
+        other => Err(Error::InvalidArgument(format!(
+            "a record must be an object, got `{other}`"
+        ))),
+    }
+}
+
+/// Return the string representation of a record value.
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Define how to handle several records producing the same output name.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DuplicatePolicy {
+    /// Append a numeric suffix to the duplicate names, like `austin-2`.
+    #[default]
+    Suffix,
+    /// Fail on the first duplicate name.
+    Error,
+}
+
+/// Define the options used to render an SVG template.
+#[derive(Default, Clone)]
+pub struct RenderOptions<'a> {
+    /// Exporter used to convert the rendered SVG files to PDF.
+    ///
+    /// The files are not converted if no exporter is specified.
+    pub exporter: Option<Exporter>,
+    /// Fields of the records used to name the output files.
+    pub field_based_name: Option<Vec<String>>,
+    /// Separator used to join the field values of the output file names.
+    pub separator: Option<&'a str>,
+    /// Localization applied to the templates.
+    pub localization: Option<&'a Localization>,
+    /// Fail when the template references a variable which is not defined.
+    pub strict: bool,
+    /// Policy applied when several records produce the same output name.
+    pub duplicates: DuplicatePolicy,
+}
+
 /// Render an SVG template.
 ///
-/// Merges the data from the CSV file into the SVG template to create a new SVG
-/// file and render it to PDF.
+/// Merges the records from the `data` source into the SVG template to create
+/// new SVG files and render them to PDF.
 ///
-/// The `field_based_name` argument can be used to specify one or several fields
-/// from the CSV file that must be used to name the output files. If the fields
-/// don't exist, this function will panic. Once all the fields are being
-/// collected, they are transformed to lowercase and concatenated together using
-/// the `separator`, in the order they were specified.
+/// The `field_based_name` option can be used to specify one or several fields
+/// from the records that must be used to name the output files. If a field
+/// does not exist, an error is returned. Once all the fields are being
+/// collected, they are slugified with [`slugify`] and concatenated together
+/// using the `separator`, in the order they were specified.
 ///
-/// If `field_based_name` is not specified, it defaults to the first field of a
-/// CSV record.
+/// If `field_based_name` is not specified, it defaults to the first field of
+/// each record.
 ///
 /// If `separator` is not specified, it defaults to dash (`-`).
 ///
+/// If several records produce the same output name, the `duplicates` policy
+/// either makes the names unique with a numeric suffix, or returns an error.
+///
+/// If a `localization` is specified, its translation functions and filters are
+/// available in the template, and the locale of each record is selected
+/// according to the localization settings. Otherwise the translation functions
+/// return the message keys.
+///
+/// If `strict` is set, rendering a template referencing a variable missing
+/// from a record fails instead of producing an empty string.
+///
 /// ```no_run
 /// # use color_eyre::{eyre::Report, Result};
 /// use std::path::Path;
-/// use bnacore::template::{render, Exporter};
+/// use bnacore::template::{render, DataSource, Exporter, RenderOptions};
 ///
 /// # fn main() -> Result<(), Report> {
 /// let svg_template = Path::new("SVG_TEMPLATE_FILENAME");
+/// let data = DataSource::from_path(Path::new("DATA_FILENAME.csv"))?;
 /// let output_dir = Path::new("OUTPUT_DIR");
 /// let fields = vec![
 ///     String::from("country"),
 ///     String::from("state"),
 ///     String::from("city"),
 /// ];
-/// let _ = render(
-///     &svg_template.canonicalize()?,
-///     output_dir,
-///     Some(Exporter::CairoSVG),
-///     Some(fields),
-///     None,
-/// )?;
+/// let options = RenderOptions {
+///     exporter: Some(Exporter::CairoSVG),
+///     field_based_name: Some(fields),
+///     ..Default::default()
+/// };
+/// let _ = render(&svg_template.canonicalize()?, &data, output_dir, &options)?;
 /// # Ok(())
 /// # }
 /// ```
 pub fn render(
     svg_template: &Path,
+    data: &DataSource,
     output_dir: &Path,
-    exporter: Option<Exporter>,
-    field_based_name: Option<Vec<String>>,
-    separator: Option<&str>,
+    options: &RenderOptions,
 ) -> Result<(), Error> {
-    // Locate the template file data and the prepare the output directory.
-    let template_data = svg_template.with_extension("csv");
+    // Prepare the output directory.
     fs::create_dir_all(output_dir)?;
 
     // Load the template.
@@ -75,53 +284,164 @@ pub fn render(
         .expect("Invalid template name.")
         .to_str()
         .unwrap();
-    let mut env = Environment::new();
+    let mut env = environment(options.localization, options.strict);
     env.add_template(name, &source)?;
     let tmpl = env.get_template(name).unwrap();
 
     // Set the separator.
-    let sep = separator.unwrap_or("-");
+    let sep = options.separator.unwrap_or("-");
 
-    // Read the CSV.
-    let mut csv_reader = Reader::from_path(template_data)?;
+    // Read the data.
+    let records = data.records()?;
+    let mut names = OutputNames::new(options.duplicates);
     let mut files: Vec<PathBuf> = Vec::new();
-    for result in csv_reader.deserialize() {
-        let record: Record = result?;
-        let mut item_name = String::new();
-        if let Some(fields) = &field_based_name {
-            let field_values = fields
-                .iter()
-                .map(|f| record[f].clone())
-                .collect::<Vec<String>>();
-            let name = field_values.join(sep);
-            item_name = name
-                .to_lowercase()
-                .replace(' ', "_")
-                .chars()
-                .filter(|c| c.is_alphabetic() || *c == '-' || *c == '_' || *c == '.')
-                .collect::<String>();
-        }
+    for (index, record) in records.into_iter().enumerate() {
+        let item_name = names.allocate(output_name(
+            &record,
+            options.field_based_name.as_deref(),
+            sep,
+            index,
+        )?)?;
         let mut item = item_name.clone();
         item.push_str(".svg");
 
         // Render the template to file for this specific record.
-        let rendered = tmpl.render(&record)?;
+        let rendered = match options.localization {
+            Some(l10n) => tmpl.render(l10n.localize(&record)?)?,
+            None => tmpl.render(&record)?,
+        };
         let output_file = output_dir.join(&item);
         fs::write(&output_file, rendered)?;
         files.push(output_file);
     }
 
     // Convert it to pdf.
-    if let Some(exporter) = exporter {
-        match exporter {
-            Exporter::Inkscape => export_with_inkscape(&files),
-            Exporter::CairoSVG => export_with_cairosvg(&files),
-            Exporter::SVG2PDF => export_with_svg2pdf(&files),
-        }
+    if let Some(exporter) = options.exporter {
+        export(exporter, &files);
     }
     Ok(())
 }
 
+/// Transform a value into a string which can safely be used in a file name.
+///
+/// The value is slugified with [`slug::slugify`], and its words are joined with
+/// underscores so that they cannot be confused with the separator of the
+/// fields.
+///
+/// ```
+/// use bnacore::template::slugify;
+///
+/// assert_eq!(slugify("Zürich"), "zurich");
+/// assert_eq!(slugify("Fort Worth"), "fort_worth");
+/// assert_eq!(slugify("St. Louis Park 2"), "st_louis_park_2");
+/// ```
+pub fn slugify(value: &str) -> String {
+    slug::slugify(value).replace('-', "_")
+}
+
+/// Sanitize a value to use it in a file name, like the former brochure
+/// pipeline did.
+///
+/// The value is lowercased and its spaces are replaced by underscores. Only the
+/// alphabetic characters, dashes, underscores and dots are kept. This filter is
+/// available in the templates as `sanitize`, to keep the existing file names
+/// and S3 keys unchanged.
+///
+/// ```
+/// use bnacore::template::sanitize;
+///
+/// assert_eq!(sanitize("Zürich"), "zürich");
+/// assert_eq!(sanitize("Coeur d'Alene"), "coeur_dalene");
+/// assert_eq!(sanitize("St. Louis Park 2"), "st._louis_park_");
+/// assert_eq!(sanitize("../etc/passwd"), "..etcpasswd");
+/// ```
+pub fn sanitize(value: &str) -> String {
+    value
+        .to_lowercase()
+        .replace(' ', "_")
+        .chars()
+        .filter(|c| c.is_alphabetic() || *c == '-' || *c == '_' || *c == '.')
+        .collect()
+}
+
+/// Build the output name of a record, without extension.
+///
+/// The name is made of the slugified values of the `fields`, or of the first
+/// field of the record if no fields are specified. It falls back to the
+/// 1-based position of the record if the name would be empty.
+fn output_name(
+    record: &Record,
+    fields: Option<&[String]>,
+    separator: &str,
+    index: usize,
+) -> Result<String, Error> {
+    let values = match fields {
+        Some(fields) => fields
+            .iter()
+            .map(|f| {
+                record.get(f).map(value_to_string).ok_or_else(|| {
+                    Error::InvalidArgument(format!("the field `{f}` does not exist"))
+                })
+            })
+            .collect::<Result<Vec<String>, Error>>()?,
+        None => record.values().take(1).map(value_to_string).collect(),
+    };
+    let name = values
+        .iter()
+        .map(|v| slugify(v))
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<String>>()
+        .join(separator);
+    if name.is_empty() {
+        return Ok((index + 1).to_string());
+    }
+    Ok(name)
+}
+
+/// Keep track of the output names already in use.
+struct OutputNames {
+    policy: DuplicatePolicy,
+    used: HashSet<String>,
+}
+
+impl OutputNames {
+    fn new(policy: DuplicatePolicy) -> Self {
+        Self {
+            policy,
+            used: HashSet::new(),
+        }
+    }
+
+    /// Reserve a name, applying the duplicate policy if it is already used.
+    fn allocate(&mut self, name: String) -> Result<String, Error> {
+        if self.used.insert(name.clone()) {
+            return Ok(name);
+        }
+        match self.policy {
+            DuplicatePolicy::Error => Err(Error::DuplicateName(name)),
+            DuplicatePolicy::Suffix => {
+                let unique = (2..)
+                    .map(|i| format!("{name}-{i}"))
+                    .find(|candidate| !self.used.contains(candidate))
+                    .unwrap();
+                self.used.insert(unique.clone());
+                Ok(unique)
+            }
+        }
+    }
+}
+
+/// Exports SVG files to PDF with a specific exporter.
+///
+/// Each PDF file is created next to its SVG file, with the same name.
+pub fn export(exporter: Exporter, srcs: &[PathBuf]) {
+    match exporter {
+        Exporter::Inkscape => export_with_inkscape(srcs),
+        Exporter::CairoSVG => export_with_cairosvg(srcs),
+        Exporter::SVG2PDF => export_with_svg2pdf(srcs),
+    }
+}
+
 /// Render the template using a record from the CSV file.
 ///
 /// ```no_run
@@ -138,13 +458,110 @@ pub fn render(
 /// # }
 /// ```
 pub fn render_record<S: Serialize>(template: &str, record: S) -> Result<String, Error> {
+    render_record_with(template, record, &RenderOptions::default())
+}
+
+/// Render the template using a record, with the localization and strictness
+/// of the render options.
+///
+/// The other options only apply to [`render`] and are ignored.
+pub fn render_record_with<S: Serialize>(
+    template: &str,
+    record: S,
+    options: &RenderOptions,
+) -> Result<String, Error> {
     let name = "template";
-    let mut env = Environment::new();
+    let mut env = environment(options.localization, options.strict);
     env.add_template(name, template)?;
     let tmpl = env.get_template(name).unwrap();
 
     // Render the template to file for this specific record.
-    Ok(tmpl.render(&record)?)
+    match options.localization {
+        Some(l10n) => Ok(tmpl.render(l10n.localize(&record)?)?),
+        None => Ok(tmpl.render(&record)?),
+    }
+}
+
+/// Report the variables referenced by a template.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LintReport {
+    /// Variables referenced by the template.
+    pub referenced: BTreeSet<String>,
+    /// Referenced variables which are not part of the known fields.
+    pub undefined: BTreeSet<String>,
+}
+
+impl LintReport {
+    /// Return true if all the referenced variables are defined.
+    pub fn is_ok(&self) -> bool {
+        self.undefined.is_empty()
+    }
+}
+
+/// Lint a template against a set of known fields.
+///
+/// Lists all the top-level variables referenced by the template, and reports
+/// the ones which are not part of the `fields`. The functions registered by
+/// the `localization` and the locale variable it injects are not reported.
+///
+/// ```
+/// use bnacore::template::lint;
+///
+/// let fields = ["ci".to_string(), "ra".to_string()];
+/// let report = lint("{{ ci }}: {{ rasc }}", &fields, None).unwrap();
+/// assert!(!report.is_ok());
+/// assert!(report.undefined.contains("rasc"));
+/// ```
+pub fn lint<S: AsRef<str>>(
+    template: &str,
+    fields: &[S],
+    localization: Option<&Localization>,
+) -> Result<LintReport, Error> {
+    let name = "template";
+    let mut env = environment(localization, false);
+    env.add_template(name, template)?;
+    let tmpl = env.get_template(name).unwrap();
+
+    let globals = env.globals().map(|(k, _)| k).collect::<HashSet<&str>>();
+    let referenced = tmpl
+        .undeclared_variables(false)
+        .into_iter()
+        .filter(|v| !globals.contains(v.as_str()))
+        .filter(|v| localization.is_none() || v != LOCALE_VARIABLE)
+        .collect::<BTreeSet<String>>();
+    let known = fields.iter().map(AsRef::as_ref).collect::<HashSet<&str>>();
+    let undefined = referenced
+        .iter()
+        .filter(|v| !known.contains(v.as_str()))
+        .cloned()
+        .collect::<BTreeSet<String>>();
+    Ok(LintReport {
+        referenced,
+        undefined,
+    })
+}
+
+/// Create a MiniJinja environment, with the `sanitize` filter and the
+/// localization functions.
+///
+/// Without a `localization`, the functions use an empty catalog: the messages
+/// fall back to their keys, and the numbers are formatted for the default
+/// locale. Undefined variables are errors in `strict` mode.
+fn environment<'source>(localization: Option<&Localization>, strict: bool) -> Environment<'source> {
+    let mut env = Environment::new();
+    if strict {
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+    }
+    env.add_filter("sanitize", |value: &str| sanitize(value));
+    match localization {
+        Some(l10n) => l10n.register(&mut env),
+        None => Localization::new(
+            Catalog::new(DEFAULT_LOCALE),
+            LocaleSelection::Fixed(DEFAULT_LOCALE.to_string()),
+        )
+        .register(&mut env),
+    }
+    env
 }
 
 /// Render a template file using a record from the CSV file.
@@ -255,6 +672,8 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{scorecard::shortscorecard::ShortScoreCard, serde_util::struct_fields};
+    use std::collections::HashMap;
 
     #[test]
     fn test_get_in_out_file() {
@@ -263,4 +682,135 @@ mod tests {
         assert_eq!(in_svg, String::from("brochure.svg"));
         assert_eq!(out_pdf, String::from("brochure.pdf"));
     }
+
+    #[test]
+    fn test_read_records_csv() {
+        let data = "ci,po\nAustin,961855\n";
+        let records = read_records(data.as_bytes(), DataFormat::Csv).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["po"], Value::String("961855".to_string()));
+    }
+
+    #[test]
+    fn test_read_records_json() {
+        let data = r#"[{"ci": "Austin", "po": 961855}, {"ci": "Zürich", "po": 421878}]"#;
+        let records = read_records(data.as_bytes(), DataFormat::Json).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["po"], Value::from(961855));
+        assert_eq!(records[1]["ci"], Value::from("Zürich"));
+    }
+
+    #[test]
+    fn test_read_records_json_lines() {
+        let data = "{\"ci\": \"Austin\", \"ra\": 47.5}\n\n{\"ci\": \"Houston\", \"ra\": 22.0}\n";
+        let records = read_records(data.as_bytes(), DataFormat::JsonLines).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["ra"], Value::from(47.5));
+    }
+
+    #[test]
+    fn test_read_records_json_not_an_array() {
+        let data = r#"{"ci": "Austin"}"#;
+        let res = read_records(data.as_bytes(), DataFormat::Json);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_typed_values_render() {
+        let source = DataSource::from_records([serde_json::json!({"ra": 47})]).unwrap();
+        let records = source.records().unwrap();
+        let rendered = render_record("{% if ra > 40 %}high{% endif %}", &records[0]).unwrap();
+        assert_eq!(rendered, "high");
+    }
+
+    #[test]
+    fn test_lint_undefined_variables() {
+        let fields = ["ci", "ra"];
+        let report = lint(
+            "{{ ci }} {{ rasc }} {% for i in range(2) %}{{ i }}{% endfor %}",
+            &fields,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            report.referenced,
+            BTreeSet::from(["ci".to_string(), "rasc".to_string()])
+        );
+        assert_eq!(report.undefined, BTreeSet::from(["rasc".to_string()]));
+    }
+
+    #[test]
+    fn test_lint_against_struct() {
+        let fields = struct_fields::<ShortScoreCard>();
+        let report = lint("{{ ci }}, {{ st }}: {{ rasc }}", fields, None).unwrap();
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn test_render_without_localization() {
+        let record = HashMap::from([("po", "188737")]);
+        assert_eq!(
+            render_record("{{ t(\"population\") }}: {{ po | number }}", &record).unwrap(),
+            "population: 188,737"
+        );
+    }
+
+    #[test]
+    fn test_strict_render() {
+        let options = RenderOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let record = HashMap::from([("ra", 47)]);
+        assert!(render_record_with("{{ rasc }}", &record, &options).is_err());
+        assert_eq!(render_record("{{ rasc }}", &record).unwrap(), "");
+    }
+
+    #[test]
+    fn test_read_records_csv_keeps_header_order() {
+        let data = "po,ci\n961855,Austin\n";
+        let records = read_records(data.as_bytes(), DataFormat::Csv).unwrap();
+        assert_eq!(records[0].keys().collect::<Vec<_>>(), vec!["po", "ci"]);
+    }
+
+    #[test]
+    fn test_output_name() {
+        let record = serde_json::json!({"ci": "Zürich", "co": "Switzerland", "po": 421878});
+        let record = record.as_object().unwrap();
+        let fields = vec!["co".to_string(), "ci".to_string()];
+        assert_eq!(
+            output_name(record, Some(&fields), "-", 0).unwrap(),
+            "switzerland-zurich"
+        );
+        assert_eq!(output_name(record, None, "-", 0).unwrap(), "zurich");
+        let missing = vec!["st".to_string()];
+        assert!(output_name(record, Some(&missing), "-", 0).is_err());
+    }
+
+    #[test]
+    fn test_output_name_empty() {
+        let record = serde_json::json!({"ci": "???"});
+        assert_eq!(
+            output_name(record.as_object().unwrap(), None, "-", 4).unwrap(),
+            "5"
+        );
+    }
+
+    #[test]
+    fn test_output_names_suffix() {
+        let mut names = OutputNames::new(DuplicatePolicy::Suffix);
+        assert_eq!(names.allocate("austin-2".to_string()).unwrap(), "austin-2");
+        assert_eq!(names.allocate("austin".to_string()).unwrap(), "austin");
+        assert_eq!(names.allocate("austin".to_string()).unwrap(), "austin-3");
+    }
+
+    #[test]
+    fn test_output_names_error() {
+        let mut names = OutputNames::new(DuplicatePolicy::Error);
+        names.allocate("austin".to_string()).unwrap();
+        assert!(matches!(
+            names.allocate("austin".to_string()),
+            Err(Error::DuplicateName(_))
+        ));
+    }
 }
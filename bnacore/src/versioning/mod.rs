@@ -1,7 +1,9 @@
 /// Represents the Calver version scheme (calver.org).
 ///
 /// Currently only the "Ubuntu" version of the scheme is supported (YY.0M[.Micro]).
-#[derive(Clone, Debug, PartialEq, PartialOrd)]
+///
+/// The versions are ordered numerically, by year, month, and micro part.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Calver {
     /// Short year - 6, 16, 106
     short_year: String,
@@ -43,6 +45,66 @@ impl Calver {
         version
     }
 
+    /// Return the micro part of the version, 0 if there is none.
+    pub fn micro(&self) -> u32 {
+        self.micro
+            .as_deref()
+            .and_then(|m| m.parse::<u32>().ok())
+            .unwrap_or_default()
+    }
+
+    /// Return a copy of the version with a specific micro part.
+    ///
+    /// A micro part of 0 is omitted.
+    pub fn with_micro(&self, micro: u32) -> Self {
+        Self {
+            micro: (micro > 0).then(|| micro.to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// Return true if both versions share the same year and month.
+    pub fn same_month(&self, other: &Calver) -> bool {
+        self.short_year == other.short_year && self.zero_padded_month == other.zero_padded_month
+    }
+
+    /// Compute the next version for the year and month of this version.
+    ///
+    /// Only the `existing` versions sharing the same year and month are
+    /// considered. If there is none, the version without micro part is
+    /// returned, otherwise the highest micro part is incremented.
+    ///
+    /// ```
+    /// use bnacore::versioning::Calver;
+    ///
+    /// let target = Calver::try_from_ubuntu("24.06").unwrap();
+    /// let existing = ["24.05.6", "24.06", "24.06.1"]
+    ///     .iter()
+    ///     .map(|v| Calver::try_from_ubuntu(v).unwrap())
+    ///     .collect::<Vec<Calver>>();
+    /// assert_eq!(target.next(&existing).to_ubuntu(), "24.06.2");
+    /// ```
+    pub fn next(&self, existing: &[Calver]) -> Calver {
+        match existing
+            .iter()
+            .filter(|c| self.same_month(c))
+            .map(Calver::micro)
+            .max()
+        {
+            Some(micro) => self.with_micro(micro + 1),
+            None => self.with_micro(0),
+        }
+    }
+
+    /// Return the numeric parts of the version, used to order the versions.
+    fn parts(&self) -> (u32, u32, u32) {
+        (
+            self.short_year.parse().unwrap_or_default(),
+            self.zero_padded_month.parse().unwrap_or_default(),
+            self.micro(),
+        )
+    }
+
     fn short_year_from_str(year: &str) -> Result<String, String> {
         let y = year.parse::<u8>().map_err(|e| e.to_string())?;
         match y {
@@ -83,6 +145,18 @@ impl Calver {
     }
 }
 
+impl Ord for Calver {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.parts().cmp(&other.parts())
+    }
+}
+
+impl PartialOrd for Calver {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -107,6 +181,9 @@ mod tests {
     #[case("24.1", "23.1", true)]
     #[case("24.2", "23.1", true)]
     #[case("23.1", "24.1", false)]
+    #[case("24.10", "24.9", true)]
+    #[case("24.05.10", "24.05.9", true)]
+    #[case("24.05", "24.05.1", false)]
     fn test_compare_ubuntu_calver(
         #[case] version: &str,
         #[case] other: &str,
@@ -0,0 +1,256 @@
+use bnacore::{
+    aws::{get_aws_parameter_value, get_aws_secrets_value},
+    neon::{self, model::Branch, BranchFilter},
+};
+use bnalambdas::{bna_api_client, BrokenspokePipeline};
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use time::{Duration, OffsetDateTime};
+use tracing::info;
+
+/// Age after which an analysis branch is considered orphaned.
+const DEFAULT_MAX_AGE_HOURS: i64 = 48;
+
+#[derive(Deserialize)]
+struct TaskInput {
+    /// List the branches which would be deleted, without deleting them.
+    #[serde(default)]
+    dry_run: bool,
+    /// Delete the branches older than this number of hours.
+    #[serde(default = "default_max_age_hours")]
+    max_age_hours: i64,
+}
+
+fn default_max_age_hours() -> i64 {
+    DEFAULT_MAX_AGE_HOURS
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum SweepReason {
+    /// The branch is older than the threshold.
+    Expired,
+    /// The pipeline which created the branch is torn down.
+    PipelineTornDown,
+}
+
+#[derive(Debug, Serialize)]
+struct SweptBranch {
+    id: String,
+    name: Option<String>,
+    reason: SweepReason,
+}
+
+#[derive(Serialize)]
+struct TaskOutput {
+    dry_run: bool,
+    branches: Vec<SweptBranch>,
+}
+
+/// Select the branches to delete.
+///
+/// Only the branches identified as analysis branches are considered: the
+/// ones named like them, or referenced by a pipeline. The primary branch and
+/// the branches which are the parents of other branches are never selected.
+///
+/// A branch is selected if its pipeline is torn down, or if it was created
+/// before `created_before` and no running pipeline uses it.
+fn select_branches(
+    branches: Vec<Branch>,
+    pipelines: &[BrokenspokePipeline],
+    created_before: OffsetDateTime,
+) -> Vec<SweptBranch> {
+    let referenced = pipelines
+        .iter()
+        .filter_map(|p| p.neon_branch_id.as_deref())
+        .collect::<HashSet<&str>>();
+    let torn_down = pipelines
+        .iter()
+        .filter(|p| p.is_torn_down())
+        .filter_map(|p| p.neon_branch_id.as_deref())
+        .collect::<HashSet<&str>>();
+    let running = pipelines
+        .iter()
+        .filter(|p| !p.is_torn_down() && p.end_time.is_none())
+        .filter_map(|p| p.neon_branch_id.as_deref())
+        .collect::<HashSet<&str>>();
+    let parents = branches
+        .iter()
+        .filter_map(|b| b.parent_id.clone())
+        .collect::<HashSet<String>>();
+    let expired = BranchFilter {
+        created_before: Some(created_before),
+        ..Default::default()
+    };
+    branches
+        .into_iter()
+        .filter(|b| !b.is_primary())
+        .filter_map(|b| {
+            let id = b.id.clone()?;
+            if parents.contains(&id) || !(b.is_analysis() || referenced.contains(id.as_str())) {
+                return None;
+            }
+            let reason = if torn_down.contains(id.as_str()) {
+                SweepReason::PipelineTornDown
+            } else if expired.matches(&b) && !running.contains(id.as_str()) {
+                SweepReason::Expired
+            } else {
+                return None;
+            };
+            Some(SweptBranch {
+                id,
+                name: b.name,
+                reason,
+            })
+        })
+        .collect()
+}
+
+async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<TaskOutput, Error> {
+    let dry_run = event.payload.dry_run;
+    let created_before = OffsetDateTime::now_utc() - Duration::hours(event.payload.max_age_hours);
+
+    // Retrieve the pipelines.
+    info!("Retrieving pipelines...");
+    let api_hostname = get_aws_parameter_value("BNA_API_HOSTNAME").await?;
+    let api = bna_api_client(&api_hostname)
+        .await
+        .map_err(|e| format!("cannot authenticate service account: {e}"))?;
+    let pipelines = api.get_pipelines().await?;
+
+    // Create the Neon HTTP client.
+    info!("Creating Neon client...");
+    let api_key = get_aws_secrets_value("NEON_API_KEY", "NEON_API_KEY").await?;
+    let project_id = get_aws_parameter_value("NEON_BROKENSPOKE_ANALYZER_PROJECT").await?;
+    let neon = neon::Client::new(&api_key, &project_id)?;
+
+    // Select the orphaned branches.
+    info!("Listing branches...");
+    let branches = neon.find_branches(&BranchFilter::default()).await?;
+    let swept = select_branches(branches, &pipelines, created_before);
+
+    // Delete them.
+    for branch in &swept {
+        if dry_run {
+            info!("Would delete branch {} ({:?}).", branch.id, branch.reason);
+            continue;
+        }
+        info!("Deleting branch {} ({:?})...", branch.id, branch.reason);
+        match neon.delete_branch(&branch.id).await {
+            Ok(_) => {}
+            Err(e) if e.is_not_found() => info!("The branch was already deleted."),
+            Err(e) => return Err(Box::new(e)),
+        }
+    }
+
+    Ok(TaskOutput {
+        dry_run,
+        branches: swept,
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        // disable printing the name of the module in every log line.
+        .with_target(false)
+        // disabling time is handy because CloudWatch will add the ingestion time.
+        .without_time()
+        .init();
+
+    run(service_fn(function_handler)).await.map_err(|e| {
+        info!("{e}");
+        e
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn branch(id: &str, name: &str, created_at: OffsetDateTime, primary: bool) -> Branch {
+        Branch {
+            id: Some(id.to_string()),
+            name: Some(name.to_string()),
+            created_at: Some(created_at),
+            primary: Some(primary),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_deserialize_input() {
+        let deserialized = serde_json::from_str::<TaskInput>("{}").unwrap();
+        assert!(!deserialized.dry_run);
+        assert_eq!(deserialized.max_age_hours, DEFAULT_MAX_AGE_HOURS);
+    }
+
+    #[test]
+    fn test_select_branches() {
+        let old = datetime!(2024-05-01 0:00 UTC);
+        let recent = datetime!(2024-05-03 0:00 UTC);
+        let mut branches = vec![
+            branch("br-main", "main", datetime!(2024-01-01 0:00 UTC), true),
+            branch("br-old", "analysis-usa-austin-texas", old, false),
+            // Not named like an analysis branch, but referenced by a pipeline.
+            branch(
+                "br-torn-down",
+                "usa-provincetown-massachusetts",
+                recent,
+                false,
+            ),
+            branch(
+                "br-ended",
+                "analysis-usa-santa-rosa-new-mexico",
+                recent,
+                false,
+            ),
+            branch("br-running", "analysis-usa-running-texas", recent, false),
+            // Still used by a long running pipeline.
+            branch("br-running-old", "analysis-usa-slow-texas", old, false),
+            // Created by hand.
+            branch("br-manual", "remy-is-testing", old, false),
+            // The parent of another branch.
+            branch("br-snapshot", "analysis-snapshot", old, false),
+            branch("br-child", "remy-is-testing-too", recent, false),
+        ];
+        branches[8].parent_id = Some("br-snapshot".to_string());
+        let pipelines = vec![
+            BrokenspokePipeline {
+                neon_branch_id: Some("br-torn-down".to_string()),
+                end_time: Some(datetime!(2024-05-03 1:00 UTC)),
+                torn_down: Some(true),
+                ..Default::default()
+            },
+            // Ended, but still waiting for the teardown.
+            BrokenspokePipeline {
+                neon_branch_id: Some("br-ended".to_string()),
+                end_time: Some(datetime!(2024-05-03 1:00 UTC)),
+                ..Default::default()
+            },
+            BrokenspokePipeline {
+                neon_branch_id: Some("br-running".to_string()),
+                ..Default::default()
+            },
+            BrokenspokePipeline {
+                neon_branch_id: Some("br-running-old".to_string()),
+                ..Default::default()
+            },
+        ];
+        let swept = select_branches(branches, &pipelines, datetime!(2024-05-02 0:00 UTC));
+        let swept = swept
+            .iter()
+            .map(|b| (b.id.as_str(), b.reason))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            swept,
+            vec![
+                ("br-old", SweepReason::Expired),
+                ("br-torn-down", SweepReason::PipelineTornDown)
+            ]
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use bnacore::aws::{get_aws_parameter_value, s3::create_calver_s3_directories};
+use bnacore::{
+    aws::{get_aws_parameter_value, s3::create_calver_directories},
+    storage::StorageBackend,
+};
 use bnalambdas::AnalysisParameters;
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
 use serde::{Deserialize, Serialize};
@@ -24,8 +27,9 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<TaskOutput, E
 
     // Read the task inputs.
     info!("Creating S3 directory...");
-    let dir = create_calver_s3_directories(
-        &bna_bucket,
+    let storage = StorageBackend::from_env(&bna_bucket).await;
+    let dir = create_calver_directories(
+        &storage,
         analysis_parameters.country.as_str(),
         analysis_parameters.city.as_str(),
         analysis_parameters.region.as_deref(),
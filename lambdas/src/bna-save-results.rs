@@ -1,19 +1,24 @@
 use aws_config::BehaviorVersion;
 use aws_smithy_types_convert::date_time::DateTimeExt;
-use bnacore::aws::get_aws_parameter_value;
+use bnacore::{
+    aws::get_aws_parameter_value,
+    bna_api::{
+        BNACoreServices, BNAFeatures, BNAInfrastructure, BNAOpportunity, BNAPost, BNARecreation,
+        BNASummary, City,
+    },
+    storage::{Storage, StorageBackend},
+};
 use bnalambdas::{
-    authenticate_service_account, update_pipeline, AnalysisParameters, BrokenspokePipeline,
-    BrokenspokeState, Context, Fargate, AWSS3,
+    bna_api_client, AnalysisParameters, BrokenspokePipeline, BrokenspokeState, Context, Fargate,
+    AWSS3,
 };
 use csv::ReaderBuilder;
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
-use reqwest::blocking::Client;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use simple_error::SimpleError;
-use std::{collections::HashMap, io::Write};
-use time::OffsetDateTime;
+use std::collections::HashMap;
 use tracing::info;
 use uuid::Uuid;
 
@@ -54,80 +59,6 @@ impl OverallScores {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNASummary {
-    pub bna_uuid: Uuid,
-    pub version: String,
-    pub city_id: Uuid,
-    pub score: f64,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNAInfrastructure {
-    pub low_stress_miles: Option<f64>,
-    pub high_stress_miles: Option<f64>,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNARecreation {
-    pub community_centers: Option<f64>,
-    pub parks: Option<f64>,
-    pub recreation_trails: Option<f64>,
-    pub score: f64,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNAOpportunity {
-    pub employment: Option<f64>,
-    pub higher_education: Option<f64>,
-    pub k12_education: Option<f64>,
-    pub technical_vocational_college: Option<f64>,
-    pub score: f64,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNACoreServices {
-    pub dentists: Option<f64>,
-    pub doctors: Option<f64>,
-    pub grocery: Option<f64>,
-    pub hospitals: Option<f64>,
-    pub pharmacies: Option<f64>,
-    pub social_services: Option<f64>,
-    pub score: f64,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNAFeatures {
-    pub people: Option<f64>,
-    pub retail: Option<f64>,
-    pub transit: Option<f64>,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BNAPost {
-    pub core_services: BNACoreServices,
-    pub features: BNAFeatures,
-    pub infrastructure: BNAInfrastructure,
-    pub opportunity: BNAOpportunity,
-    pub recreation: BNARecreation,
-    pub summary: BNASummary,
-}
-
-#[derive(Serialize, Deserialize, Default)]
-pub struct City {
-    pub city_id: Option<Uuid>,
-    pub country: String,
-    pub state: String,
-    pub name: String,
-    pub latitude: Option<f64>,
-    pub longitude: Option<f64>,
-    pub region: Option<String>,
-    pub state_abbrev: Option<String>,
-    pub speed_limit: Option<i32>,
-    pub created_at: Option<OffsetDateTime>,
-    pub updated_at: Option<OffsetDateTime>,
-}
-
 async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
     // Read the task inputs.
     info!("Reading input...");
@@ -145,16 +76,16 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
     let bna_bucket = get_aws_parameter_value("BNA_BUCKET").await?;
 
     // Authenticate the service account.
-    let auth = authenticate_service_account()
+    let api = bna_api_client(&api_hostname)
         .await
         .map_err(|e| format!("cannot authenticate service account: {e}"))?;
 
     // Prepare the AWS configuration.
     let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
 
-    // Configure the S3 client.
-    info!("Configure the S3 client...");
-    let s3_client = aws_sdk_s3::Client::new(&config);
+    // Configure the storage.
+    info!("Configure the storage...");
+    let storage = StorageBackend::from_env(&bna_bucket).await;
 
     // Configure the ECS client.
     info!("Configure the ECS client...");
@@ -169,7 +100,15 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
         "Download the CSV file with the results from {}...",
         scores_csv
     );
-    let buffer = fetch_s3_object_as_bytes(&s3_client, &bna_bucket, &scores_csv).await?;
+    let buffer = match storage.get(&scores_csv).await {
+        Ok(buffer) => buffer,
+        Err(e) if e.is_not_found() => {
+            return Err(Box::new(SimpleError::new(format!(
+                "the analysis did not produce the results file {scores_csv}"
+            ))))
+        }
+        Err(e) => return Err(e.into()),
+    };
 
     // Parse the results.
     info!("Parse the results...");
@@ -183,21 +122,7 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
         None => country.to_owned(),
     };
     let name = &analysis_parameters.city;
-    let cities_url = format!("{api_hostname}/cities");
-    let get_cities_url = format!("{cities_url}/{country}/{region}/{name}");
-    let client = Client::new();
-    let r = client.get(&get_cities_url).send()?;
-    let city: Option<City> = match r.status().as_u16() {
-        x if x < 400 => Some(r.json::<City>()?),
-        404 => None,
-        _ => {
-            return Err(Box::new(SimpleError::new(format!(
-                "cannot retrieve city at {get_cities_url}: {} {}",
-                r.status(),
-                r.status().as_str()
-            ))))
-        }
-    };
+    let city = api.get_city(country, &region, name).await?;
 
     // Create city if it does not exist and save the city_id.
     // Otherwise save the city_id and update the population..
@@ -214,13 +139,7 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
             name: name.clone(),
             ..Default::default()
         };
-        let city = client
-            .post(cities_url)
-            .bearer_auth(auth.access_token.clone())
-            .json(&c)
-            .send()?
-            .error_for_status()?
-            .json::<City>()?;
+        let city = api.create_city(&c).await?;
         city_id = city.city_id.unwrap();
     }
 
@@ -228,18 +147,10 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
     let version = aws_s3.get_version();
     let bna_post = scores_to_bnapost(overall_scores, version, city_id);
 
-    // Prepare API URLs.
-    let bnas_url = format!("{api_hostname}/bnas");
-
     // Post a new entry via the API.
     info!("Post a new BNA entry via the API...");
     info!("New entry: {:?}", &bna_post);
-    Client::new()
-        .post(&bnas_url)
-        .bearer_auth(auth.access_token.clone())
-        .json(&bna_post)
-        .send()?
-        .error_for_status()?;
+    api.post_bna(&bna_post).await?;
 
     // Compute the time it took to run the fargate task.
     let describe_tasks = ecs_client
@@ -266,7 +177,6 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
     // TODO(rgreinho): Update the pipeline status when the new state will be available.
     // Update the pipeline status.
     info!("updating pipeline...");
-    let patch_url = format!("{bnas_url}/analysis/{state_machine_id}");
     let start_time = started_at
         .to_time()
         .expect("a valid start time is expected");
@@ -279,7 +189,7 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
         state: Some(BrokenspokeState::Setup),
         ..Default::default()
     };
-    update_pipeline(&patch_url, &auth, &pipeline)?;
+    api.patch_pipeline(&pipeline).await?;
 
     Ok(())
 }
@@ -343,30 +253,6 @@ fn scores_to_bnapost(overall_scores: OverallScores, version: String, city_id: Uu
     }
 }
 
-async fn fetch_s3_object_as_bytes(
-    client: &aws_sdk_s3::Client,
-    bucket: &str,
-    key: &str,
-) -> std::io::Result<Vec<u8>> {
-    let mut object = client
-        .get_object()
-        .bucket(bucket)
-        .key(key)
-        .send()
-        .await
-        .map_err(|e| {
-            std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("AWS S3 operation error: {e}"),
-            )
-        })?;
-    let mut buffer: Vec<u8> = Vec::new();
-    while let Some(bytes) = object.body.try_next().await? {
-        buffer.write_all(&bytes)?;
-    }
-    Ok(buffer)
-}
-
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     tracing_subscriber::fmt()
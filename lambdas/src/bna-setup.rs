@@ -1,9 +1,6 @@
-use bnacore::{
-    aws::{get_aws_parameter_value, get_aws_secrets_value},
-    neon,
-};
+use bnacore::{aws::get_aws_parameter_value, database::DatabaseProvisioner};
 use bnalambdas::{
-    authenticate_service_account, update_pipeline, AnalysisParameters, BrokenspokePipeline,
+    bna_api_client, database_provisioner, AnalysisParameters, BrokenspokePipeline,
     BrokenspokeState, Context,
 };
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
@@ -11,8 +8,6 @@ use serde::{Deserialize, Serialize};
 use simple_error::SimpleError;
 use tracing::info;
 
-const NEON_MAX_BRANCHES: usize = 20;
-
 #[derive(Deserialize)]
 struct TaskInput {
     analysis_parameters: AnalysisParameters,
@@ -34,12 +29,9 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<TaskOutput, E
     // Retrieve API hostname.
     let api_hostname = get_aws_parameter_value("BNA_API_HOSTNAME").await?;
 
-    // Prepare the API URL.
-    let url = format!("{api_hostname}/bnas/analysis");
-
     // Authenticate the service account.
     info!("Authenticating service account...");
-    let auth = authenticate_service_account()
+    let api = bna_api_client(&api_hostname)
         .await
         .map_err(|e| format!("cannot authenticate service account: {e}"))?;
 
@@ -51,37 +43,18 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<TaskOutput, E
 
     // Update the pipeline status.
     info!("updating pipeline...");
-    let patch_url = format!("{url}/{state_machine_id}");
     let pipeline = BrokenspokePipeline {
         state_machine_id,
         state: Some(BrokenspokeState::Setup),
         ..Default::default()
     };
-    update_pipeline(&patch_url, &auth, &pipeline)?;
-
-    // Create the Neon HTTP client.
-    info!("Creating Neon client...");
-    let api_key = get_aws_secrets_value("NEON_API_KEY", "NEON_API_KEY").await?;
-    let project_id = get_aws_parameter_value("NEON_BROKENSPOKE_ANALYZER_PROJECT").await?;
-    let neon = neon::Client::new(&api_key, &project_id)?;
-
-    // Query neon API and check whether we can create a branch or not.
-    info!("Checking database branch capacity...");
-    let branches = neon.get_branches().await?;
-
-    // Not enough capacity to proceed. Back into the queue.
-    if branches.branches.len() >= (NEON_MAX_BRANCHES + 1) {
-        return Err(Box::new(SimpleError::new(format!(
-            "Not enough capacity to proceed ({} branches). Back into the queue",
-            branches.branches.len()
-        ))));
-    }
-    info!(
-        "There is(are) {} active branch(es).",
-        branches.branches.len()
-    );
+    api.patch_pipeline(&pipeline).await?;
 
-    // Prepare new branch.
+    // Select the database provisioner.
+    info!("Creating database provisioner...");
+    let provisioner = database_provisioner().await?;
+
+    // Prepare the database name.
     let mut branch_name = format!(
         "{}-{}-",
         analysis_parameters.country, analysis_parameters.city,
@@ -91,30 +64,22 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<TaskOutput, E
         None => branch_name.push_str(&analysis_parameters.country),
     };
     branch_name = branch_name.replace(' ', "-");
-    if branches
-        .branches
-        .into_iter()
-        .filter_map(|b| b.branch.name)
-        .any(|b| branch_name == b)
-    {
-        return Err(Box::new(SimpleError::new(
-            "a branch with the same name already exists",
-        )));
-    }
 
-    // Create the neon branch.
-    info!("Creating branch {}...", branch_name);
-    let create_branch_response = neon.create_branch(&branch_name).await?;
-    info!("{:#?}", create_branch_response);
-
-    let neon_branch_id = create_branch_response.branch.id.unwrap();
-    let neon_host = create_branch_response
-        .endpoints
-        .first()
-        .unwrap()
-        .host
-        .clone()
-        .unwrap();
+    // Provision the database.
+    info!("Provisioning database {branch_name}...");
+    let database = match provisioner.provision(&branch_name).await {
+        Ok(database) => database,
+        // Not enough capacity to proceed. Back into the queue.
+        Err(e) if e.is_limit_exceeded() => {
+            return Err(Box::new(SimpleError::new(format!(
+                "Not enough capacity to proceed ({e}). Back into the queue"
+            ))))
+        }
+        Err(e) => return Err(Box::new(e)),
+    };
+    info!("{database:#?}");
+    let neon_branch_id = database.id;
+    let neon_host = database.host;
 
     // Update the pipeline status.
     let pipeline = BrokenspokePipeline {
@@ -122,7 +87,7 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<TaskOutput, E
         neon_branch_id: Some(neon_branch_id.clone()),
         ..Default::default()
     };
-    update_pipeline(&patch_url, &auth, &pipeline)?;
+    api.patch_pipeline(&pipeline).await?;
 
     // Return the ID of the created database branch.
     Ok(TaskOutput {
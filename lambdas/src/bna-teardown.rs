@@ -1,6 +1,6 @@
-use bnacore::aws::get_aws_parameter_value;
+use bnacore::{aws::get_aws_parameter_value, database::DatabaseProvisioner};
 use bnalambdas::{
-    authenticate_service_account, update_pipeline, AnalysisParameters, BrokenspokePipeline,
+    bna_api_client, database_provisioner, AnalysisParameters, BrokenspokePipeline,
     BrokenspokeState, Context,
 };
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
@@ -27,11 +27,8 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
     // Retrieve API hostname.
     let api_hostname = get_aws_parameter_value("BNA_API_HOSTNAME").await?;
 
-    // Prepare the API URL.
-    let url = format!("{api_hostname}/bnas/analysis");
-
     // Authenticate the service account.
-    let auth = authenticate_service_account()
+    let api = bna_api_client(&api_hostname)
         .await
         .map_err(|e| format!("cannot authenticate service account: {e}"))?;
 
@@ -40,13 +37,43 @@ async fn function_handler(event: LambdaEvent<TaskInput>) -> Result<(), Error> {
     let state_machine_id = state_machine_context.id;
 
     // Update the pipeline status.
-    let patch_url = format!("{url}/{state_machine_id}");
     let pipeline = BrokenspokePipeline {
         state_machine_id,
         state: Some(BrokenspokeState::Cleanup),
         ..Default::default()
     };
-    update_pipeline(&patch_url, &auth, &pipeline)?;
+    api.patch_pipeline(&pipeline).await?;
+
+    // Retrieve the database recorded by the setup, if it went that far.
+    info!("Retrieving pipeline...");
+    let recorded = api.get_pipeline(state_machine_id).await?;
+    let mut cost = recorded.cost;
+
+    // Delete the database.
+    if let Some(neon_branch_id) = recorded.neon_branch_id {
+        let provisioner = database_provisioner().await?;
+
+        // Estimate the database cost, before its consumption is lost.
+        match provisioner.cost(&neon_branch_id).await {
+            Ok(database_cost) => {
+                info!("The database cost is ${database_cost}.");
+                cost = Some(cost.unwrap_or_default() + database_cost);
+            }
+            Err(e) => info!("Cannot estimate the database cost: {e}"),
+        }
+
+        info!("Deprovisioning database {neon_branch_id}...");
+        provisioner.deprovision(&neon_branch_id).await?;
+    }
+
+    // Mark the pipeline as torn down, with the cost of the compute and the database.
+    let pipeline = BrokenspokePipeline {
+        state_machine_id,
+        cost,
+        torn_down: Some(true),
+        ..Default::default()
+    };
+    api.patch_pipeline(&pipeline).await?;
 
     Ok(())
 }
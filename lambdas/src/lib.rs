@@ -1,10 +1,20 @@
-use bnacore::aws::{get_aws_parameter_value, get_aws_secrets_value};
-use reqwest::blocking::Client;
-use rust_decimal::prelude::*;
+use bnacore::{
+    auth::{AuthBackend, ClientCredentials, StaticToken},
+    aws::{
+        get_aws_parameter_value, get_aws_secrets_as, get_aws_secrets_value, settings::load_settings,
+    },
+    bna_api::BnaApiClient,
+    database::{NeonProvisioner, NeonSettings, ProvisionerBackend},
+    neon,
+};
 use serde::{Deserialize, Serialize};
+use std::env;
 use time::OffsetDateTime;
+use tokio::sync::OnceCell;
 use uuid::Uuid;
 
+pub use bnacore::bna_api::{BrokenspokePipeline, BrokenspokeState};
+
 pub const BROKENSPOKE_ANALYZER_BUCKET: &str = "brokenspoke-analyzer";
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -70,56 +80,8 @@ impl AnalysisParameters {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub enum BrokenspokeState {
-    SqsMessage,
-    Setup,
-    Analysis,
-    Cleanup,
-}
-
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub struct BrokenspokePipeline {
-    pub cost: Option<Decimal>,
-    pub end_time: Option<OffsetDateTime>,
-    pub fargate_task_arn: Option<String>,
-    pub neon_branch_id: Option<String>,
-    pub s3_bucket: Option<String>,
-    pub scheduled_trigger_id: Option<Uuid>,
-    pub sqs_message: Option<String>,
-    pub start_time: OffsetDateTime,
-    pub state: Option<BrokenspokeState>,
-    pub state_machine_id: Uuid,
-    pub torn_down: Option<bool>,
-}
-
-impl Default for BrokenspokePipeline {
-    fn default() -> Self {
-        Self {
-            cost: Default::default(),
-            end_time: Default::default(),
-            fargate_task_arn: Default::default(),
-            neon_branch_id: Default::default(),
-            s3_bucket: Default::default(),
-            scheduled_trigger_id: Default::default(),
-            sqs_message: Default::default(),
-            start_time: OffsetDateTime::now_utc(),
-            state_machine_id: Default::default(),
-            state: Default::default(),
-            torn_down: Default::default(),
-        }
-    }
-}
-
-/// Define Cognito autnetication response.
-#[derive(Debug, Deserialize)]
-pub struct AuthResponse {
-    pub access_token: String,
-    pub expires_in: u32,
-    pub token_type: String,
-}
-
 /// Define Cognito app client credentials.
+#[derive(Deserialize)]
 pub struct AppClientCredentials {
     pub client_id: String,
     pub client_secret: String,
@@ -128,38 +90,64 @@ pub struct AppClientCredentials {
 /// Retrieve service account credentials.
 pub async fn get_service_account_credentials() -> Result<AppClientCredentials, bnacore::Error> {
     const SERVICE_ACCOUNT_CREDENTIALS: &str = "BROKENSPOKE_ANALYZER_SERVICE_ACCOUNT_CREDENTIALS";
-    let client_id = get_aws_secrets_value(SERVICE_ACCOUNT_CREDENTIALS, "client_id").await?;
-    let client_secret = get_aws_secrets_value(SERVICE_ACCOUNT_CREDENTIALS, "client_secret").await?;
-    Ok(AppClientCredentials {
-        client_id,
-        client_secret,
-    })
+    get_aws_secrets_as(SERVICE_ACCOUNT_CREDENTIALS).await
 }
 
-pub async fn authenticate(
-    credentials: &AppClientCredentials,
-) -> Result<AuthResponse, bnacore::Error> {
+/// Environment variable providing a static access token for the BNA API,
+/// instead of authenticating the service account with Cognito.
+pub const API_TOKEN_VARIABLE: &str = "BNA_API_TOKEN";
+
+/// Authentication of the service account, shared by the warm invocations.
+static SERVICE_ACCOUNT_AUTH: OnceCell<AuthBackend> = OnceCell::const_new();
+
+/// Return the authentication provider of the service account.
+///
+/// The provider is created on the first call and reused by the following
+/// invocations of a warm lambda, along with its cached access token. If the
+/// `BNA_API_TOKEN` variable is set, its value is used as a static token.
+pub async fn service_account_auth() -> Result<AuthBackend, bnacore::Error> {
     const COGNITO_HOSTNAME: &str = "BNA_COGNITO_HOSTNAME";
-    let cognito_hostname = get_aws_parameter_value(COGNITO_HOSTNAME).await?;
-    let token_endpoint = format!("{cognito_hostname}/oauth2/token");
-    Ok(Client::new()
-        .post(token_endpoint)
-        .form(&[
-            ("grant_type", "client_credentials"),
-            ("scope", "service_account/write"),
-        ])
-        .basic_auth(
-            credentials.client_id.clone(),
-            Some(credentials.client_secret.clone()),
-        )
-        .send()?
-        .error_for_status()?
-        .json::<AuthResponse>()?)
+    const SERVICE_ACCOUNT_SCOPE: &str = "service_account/write";
+    SERVICE_ACCOUNT_AUTH
+        .get_or_try_init(|| async {
+            if let Ok(token) = env::var(API_TOKEN_VARIABLE) {
+                return Ok(AuthBackend::Static(StaticToken(token)));
+            }
+            let credentials = get_service_account_credentials().await?;
+            let cognito_hostname = get_aws_parameter_value(COGNITO_HOSTNAME).await?;
+            let token_url = format!("{cognito_hostname}/oauth2/token").parse()?;
+            let provider = ClientCredentials::new(
+                token_url,
+                &credentials.client_id,
+                &credentials.client_secret,
+            )
+            .scope(SERVICE_ACCOUNT_SCOPE);
+            Ok::<_, bnacore::Error>(AuthBackend::ClientCredentials(Box::new(provider)))
+        })
+        .await
+        .cloned()
+}
+
+/// Create a BNA API client authenticated with the service account.
+///
+/// The `api_hostname` is usually read from the `BNA_API_HOSTNAME` parameter.
+pub async fn bna_api_client(api_hostname: &str) -> Result<BnaApiClient, bnacore::Error> {
+    Ok(BnaApiClient::new(api_hostname)?.with_auth(service_account_auth().await?))
 }
 
-pub async fn authenticate_service_account() -> Result<AuthResponse, bnacore::Error> {
-    let credentials = get_service_account_credentials().await?;
-    authenticate(&credentials).await
+/// Select the provisioner of the analysis databases.
+///
+/// The Neon project is used, unless the `BNA_POSTGRES_URL` variable points to a
+/// Postgres server. The Neon branches, and the prices used to estimate their
+/// cost, are configured with the [`NeonSettings`] parameters.
+pub async fn database_provisioner() -> Result<ProvisionerBackend, bnacore::Error> {
+    ProvisionerBackend::from_env(|| async {
+        let api_key = get_aws_secrets_value("NEON_API_KEY", "NEON_API_KEY").await?;
+        let project_id = get_aws_parameter_value("NEON_BROKENSPOKE_ANALYZER_PROJECT").await?;
+        let settings: NeonSettings = load_settings().await?;
+        NeonProvisioner::new(neon::Client::new(&api_key, &project_id)?).with_settings(settings)
+    })
+    .await
 }
 
 /// Define a state machine context object.
@@ -229,19 +217,6 @@ pub struct StateMachine {
     pub name: String,
 }
 
-pub fn update_pipeline(
-    url: &str,
-    auth: &AuthResponse,
-    pipeline: &BrokenspokePipeline,
-) -> Result<reqwest::blocking::Response, reqwest::Error> {
-    Client::new()
-        .patch(url)
-        .bearer_auth(auth.access_token.clone())
-        .json(&pipeline)
-        .send()?
-        .error_for_status()
-}
-
 #[derive(Deserialize, Serialize, Clone)]
 pub struct AWSS3 {
     pub destination: String,
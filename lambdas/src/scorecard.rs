@@ -1,15 +1,29 @@
-use aws_config::BehaviorVersion;
 use aws_lambda_events::event::sqs::SqsEvent;
-use aws_sdk_s3::primitives::ByteStream;
-use bnacore::combine::combine_mem;
+use bnacore::{
+    brochure::{Manifest, PdfConverter},
+    storage::{Storage, StorageBackend},
+};
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
-use minijinja::Environment;
 use serde_json::Value;
-use std::sync::Arc;
+use std::{env, path::Path, sync::Arc};
 use svg2pdf::{ConversionOptions, PageOptions};
 
 const BUCKET_NAME: &str = "brokenspoke-analyzer";
 
+/// Embedded brochure manifest, used unless `BNA_BROCHURE_MANIFEST` is set.
+const BROCHURE_MANIFEST: &str = include_str!("../../assets/brochures/scorecard.toml");
+
+/// Convert SVG documents to PDF in-process, with svg2pdf.
+struct Svg2PdfConverter {
+    fontdb: usvg::fontdb::Database,
+}
+
+impl PdfConverter for Svg2PdfConverter {
+    fn convert(&self, svg: &str) -> Result<Vec<u8>, bnacore::Error> {
+        pdf_convert(svg, &self.fontdb).map_err(bnacore::Error::Internal)
+    }
+}
+
 async fn function_handler(event: LambdaEvent<SqsEvent>) -> Result<(), Error> {
     // Load the parameters.
     let v: Value = serde_json::from_str(
@@ -20,7 +34,16 @@ async fn function_handler(event: LambdaEvent<SqsEvent>) -> Result<(), Error> {
             .as_str(),
     )?;
 
+    // Load the brochure manifest.
+    let manifest = match env::var("BNA_BROCHURE_MANIFEST") {
+        Ok(path) => Manifest::from_path(Path::new(&path))?,
+        Err(_) => embedded_manifest()?,
+    };
+
     // Compute the bucket key.
+    //
+    // The key does not use the manifest output name, to keep the existing
+    // scorecard URLs valid.
     let year = &v["year"];
     let country = &v["country"];
     let city = &v["city"];
@@ -35,46 +58,49 @@ async fn function_handler(event: LambdaEvent<SqsEvent>) -> Result<(), Error> {
     fontdb.load_fonts_dir("../assets/fonts/DharmaGothicExtended");
     fontdb.load_fonts_dir("../assets/fonts/Montserrat");
 
-    // Load the template.
-    let source_page_1 = include_str!("../../assets/visuals/template-scorecard-pg1-v23.2.svg");
-    let mut env = Environment::new();
-    env.add_template("scorecard", source_page_1).unwrap();
-
-    // Render the template to file for this specific record.
-    let template = env.get_template("scorecard").unwrap();
-    let rendered = template.render(&v)?;
-
-    // Convert it to pdf.
-    let pdf_page_1 = pdf_convert(&rendered, &fontdb)?;
-    std::fs::write("page_1.pdf", &pdf_page_1)?;
-
-    // Load the second page and convert it to pdf.
-    let source_page_2 = include_str!("../../assets/visuals/template-scorecard-pg2-v23.1.svg");
-    let pdf_page_2 = pdf_convert(source_page_2, &fontdb)?;
-    std::fs::write("page_2.pdf", &pdf_page_2)?;
-
-    // Combine the 2 pages.
-    let full_pdf = vec![pdf_page_1.as_slice(), pdf_page_2.as_slice()];
-    let mut scorecard = combine_mem(full_pdf.as_slice())?;
-    let mut buffer: Vec<u8> = Vec::new();
-    scorecard.save_to(&mut buffer)?;
-    scorecard.save("scorecard_test.pdf")?;
+    // Render all the pages of the brochure.
+    let converter = Svg2PdfConverter { fontdb };
+    let buffer = manifest.render(&v, &converter, None)?;
 
     // Upload to S3.
-    let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let client = aws_sdk_s3::Client::new(&config);
-    let body = ByteStream::from(buffer);
-    client
-        .put_object()
-        .bucket(BUCKET_NAME)
-        .key(key)
-        .body(body)
-        .send()
-        .await?;
+    let storage = StorageBackend::from_env(BUCKET_NAME).await;
+    storage.put(&key, buffer).await?;
 
     Ok(())
 }
 
+/// Build the brochure manifest from the files embedded into the binary.
+///
+/// The lambda is deployed without the assets, therefore the pages and the
+/// translation catalogs must be part of the binary.
+fn embedded_manifest() -> Result<Manifest, bnacore::Error> {
+    Ok(Manifest::from_toml(BROCHURE_MANIFEST)?
+        .embed(
+            "../visuals/template-scorecard-pg1-v23.2.svg",
+            include_bytes!("../../assets/visuals/template-scorecard-pg1-v23.2.svg"),
+        )
+        .embed(
+            "../visuals/template-scorecard-pg2-v23.1.svg",
+            include_bytes!("../../assets/visuals/template-scorecard-pg2-v23.1.svg"),
+        )
+        .embed(
+            "../locales/en-US.ftl",
+            include_bytes!("../../assets/locales/en-US.ftl"),
+        )
+        .embed(
+            "../locales/es-ES.ftl",
+            include_bytes!("../../assets/locales/es-ES.ftl"),
+        )
+        .embed(
+            "../locales/fr-FR.ftl",
+            include_bytes!("../../assets/locales/fr-FR.ftl"),
+        )
+        .embed(
+            "../locales/nl-BE.ftl",
+            include_bytes!("../../assets/locales/nl-BE.ftl"),
+        ))
+}
+
 fn pdf_convert(svg: &str, fontdb: &usvg::fontdb::Database) -> Result<Vec<u8>, String> {
     // Set rendering options.
     let options = usvg::Options {
@@ -170,4 +196,14 @@ mod tests {
 
         // let result = function_handler(event).await.unwrap();
     }
+
+    #[test]
+    fn test_embedded_manifest() {
+        let manifest = embedded_manifest().unwrap();
+        for page in &manifest.pages {
+            assert!(manifest
+                .embedded
+                .contains_key(&page.resolve(&manifest.base_dir)));
+        }
+    }
 }
@@ -16,7 +16,7 @@
 //!  xsv sample 10 shortcodes-2021-v15.csv > brochure.csv
 //! ```
 //!
-use bnacore::template::{render, Exporter};
+use bnacore::template::{render, DataSource, Exporter, RenderOptions};
 use color_eyre::{eyre::Report, Result};
 use std::path::PathBuf;
 
@@ -27,17 +27,17 @@ fn main() -> Result<(), Report> {
     // Get the paths.
     let example_dir = PathBuf::from("examples/brochures").canonicalize()?;
     let brochure_template = example_dir.join("brochure.svg").canonicalize()?;
+    let data = DataSource::Csv(example_dir.join("brochure.csv"));
     let output_dir = example_dir.join("output");
 
     // Render the template.
     let fields = vec![String::from("co"), String::from("st"), String::from("ci")];
-    render(
-        &brochure_template,
-        &output_dir,
-        Some(Exporter::Inkscape),
-        Some(fields),
-        None,
-    )?;
+    let options = RenderOptions {
+        exporter: Some(Exporter::Inkscape),
+        field_based_name: Some(fields),
+        ..Default::default()
+    };
+    render(&brochure_template, &data, &output_dir, &options)?;
 
     Ok(())
 }
@@ -1,4 +1,7 @@
-use bnacore::combine::batch_append;
+use bnacore::{
+    combine::batch_append,
+    storage::upload::{UploadTarget, Uploader},
+};
 use clap::{crate_name, ArgAction, Parser, ValueHint};
 use color_eyre::{eyre::Report, Result};
 use std::path::{Path, PathBuf};
@@ -16,9 +19,16 @@ pub struct Opts {
     /// Specify the files to append the extra document to
     #[clap( value_hint = ValueHint::FilePath)]
     pub files: Vec<PathBuf>,
+    /// Upload the updated files to an S3 location, like `s3://bucket/prefix`
+    #[clap(long, value_name = "S3_URL")]
+    pub upload: Option<UploadTarget>,
+    /// List the files which would be uploaded, without uploading them
+    #[clap(long, requires = "upload")]
+    pub dry_run: bool,
 }
 
-fn main() -> Result<(), Report> {
+#[tokio::main]
+async fn main() -> Result<(), Report> {
     // Setup the application.
     color_eyre::install()?;
 
@@ -33,5 +43,20 @@ fn main() -> Result<(), Report> {
         .collect::<Vec<&Path>>();
 
     // Combine the extra document to them all.
-    Ok(batch_append(&f, &opts.extra)?)
+    batch_append(&f, &opts.extra)?;
+
+    // Upload the updated files.
+    if let Some(target) = &opts.upload {
+        let storage = target.storage().await;
+        let uploader = Uploader {
+            storage: &storage,
+            target,
+            dry_run: opts.dry_run,
+        };
+        for entry in uploader.upload_files(Path::new(""), &opts.files).await? {
+            println!("{entry}");
+        }
+    }
+
+    Ok(())
 }
@@ -1,4 +1,7 @@
-use bnacore::bundle::{Bundle, FileType, GroupBy};
+use bnacore::{
+    bundle::{Bundle, FileType, GroupBy},
+    storage::upload::{UploadTarget, Uploader},
+};
 use clap::{crate_name, ArgAction, Parser, ValueEnum, ValueHint};
 use color_eyre::{eyre::Report, Result};
 use std::path::PathBuf;
@@ -77,9 +80,16 @@ pub struct Opts {
     /// Specify the directory containing the files to bundle.
     #[clap(value_parser, value_hint = ValueHint::DirPath)]
     pub input_dir: PathBuf,
+    /// Upload the bundles to an S3 location, like `s3://bucket/prefix`
+    #[clap(long, value_name = "S3_URL")]
+    pub upload: Option<UploadTarget>,
+    /// List the files which would be uploaded, without uploading them
+    #[clap(long, requires = "upload")]
+    pub dry_run: bool,
 }
 
-fn main() -> Result<(), Report> {
+#[tokio::main]
+async fn main() -> Result<(), Report> {
     // Setup the application.
     color_eyre::install()?;
 
@@ -88,12 +98,27 @@ fn main() -> Result<(), Report> {
 
     // Bundle the brochures.
     let bundle = Bundle {
-        input_dir: opts.input_dir,
+        input_dir: opts.input_dir.clone(),
         group_by: opts.group_by.into(),
         strict: opts.strict,
         filetype: opts.filetype.into(),
     };
 
     // Zip'em.
-    Ok(bundle.zip(false)?)
+    bundle.zip(false)?;
+
+    // Upload the bundles.
+    if let Some(target) = &opts.upload {
+        let storage = target.storage().await;
+        let uploader = Uploader {
+            storage: &storage,
+            target,
+            dry_run: opts.dry_run,
+        };
+        for entry in uploader.upload_dir(&opts.input_dir.join("bundles")).await? {
+            println!("{entry}");
+        }
+    }
+
+    Ok(())
 }
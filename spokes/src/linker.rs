@@ -0,0 +1,119 @@
+use bnacore::{
+    aws::s3::{calver_base, latest_calver_directory, presign_calver_artifacts},
+    storage::StorageBackend,
+};
+use clap::{crate_name, ArgAction, Parser, ValueEnum};
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use serde::Serialize;
+use std::{io, path::PathBuf, time::Duration};
+use time::OffsetDateTime;
+
+/// Bucket containing the analysis artifacts.
+const BUCKET_NAME: &str = "brokenspoke-analyzer";
+
+/// Define the formats of the link table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum LinkFormatArg {
+    Csv,
+    Json,
+}
+
+/// Represent a row of the link table.
+#[derive(Debug, Serialize)]
+struct LinkRow<'a> {
+    country: &'a str,
+    region: &'a str,
+    city: &'a str,
+    calver: &'a str,
+    artifact: &'a str,
+    url: &'a str,
+    #[serde(with = "time::serde::rfc3339")]
+    expires_at: OffsetDateTime,
+}
+
+// CLI options.
+#[derive(Parser, Debug)]
+#[clap(name = crate_name!(), author, about, version)]
+pub struct Opts {
+    /// Sets the verbosity level
+    #[clap(short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+    /// Specify the bucket containing the artifacts
+    #[clap(short, long, default_value = BUCKET_NAME)]
+    pub bucket: String,
+    /// Specify the validity of the links, in hours (7 days maximum)
+    #[clap(short, long, default_value_t = 168)]
+    pub expires_in: u64,
+    /// Specify the calver version, instead of using the most recent one
+    #[clap(long)]
+    pub calver: Option<String>,
+    /// Specify the format of the link table
+    #[clap(short, long, value_enum, default_value = "csv")]
+    pub format: LinkFormatArg,
+    /// Specify the region, if it is different from the country
+    #[clap(short, long)]
+    pub region: Option<String>,
+    /// Specify the country
+    pub country: String,
+    /// Specify the city
+    pub city: String,
+}
+
+// Print a table of presigned links to the artifacts of a city analysis.
+#[tokio::main]
+async fn main() -> Result<(), Report> {
+    // Setup the application.
+    color_eyre::install()?;
+
+    // Setup the CLI.
+    let opts: Opts = Opts::parse();
+
+    // Locate the calver directory.
+    let storage = StorageBackend::from_env(&opts.bucket).await;
+    let region = opts.region.as_deref();
+    let calver_dir = match &opts.calver {
+        Some(calver) => {
+            calver_base::<PathBuf>(&opts.country, &opts.city, region, Some(calver), None)
+        }
+        None => latest_calver_directory(&storage, &opts.country, &opts.city, region)
+            .await?
+            .ok_or_else(|| eyre!("no analysis found for {}, {}", opts.city, opts.country))?,
+    };
+    let calver = calver_dir
+        .file_name()
+        .and_then(|c| c.to_str())
+        .unwrap_or_default();
+
+    // Generate the links.
+    let expires_in = Duration::from_secs(opts.expires_in * 3600);
+    let links = presign_calver_artifacts(&storage, &calver_dir, expires_in).await?;
+    let rows = links
+        .iter()
+        .map(|link| LinkRow {
+            country: &opts.country,
+            region: region.unwrap_or(&opts.country),
+            city: &opts.city,
+            calver,
+            artifact: &link.artifact,
+            url: &link.url,
+            expires_at: link.expires_at,
+        })
+        .collect::<Vec<LinkRow>>();
+
+    // Print the link table.
+    match opts.format {
+        LinkFormatArg::Csv => {
+            let mut writer = csv::Writer::from_writer(io::stdout());
+            for row in &rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+        }
+        LinkFormatArg::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+    }
+
+    Ok(())
+}
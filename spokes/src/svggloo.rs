@@ -1,8 +1,17 @@
-use bnacore::template::{render, Exporter};
+use bnacore::{
+    i18n::{Catalog, LocaleSelection, Localization, DEFAULT_LOCALE},
+    scorecard::{scorecard24::ScoreCard24, shortscorecard::ShortScoreCard},
+    serde_util::struct_fields,
+    storage::upload::{UploadTarget, Uploader},
+    template::{lint, render, DataFormat, DataSource, DuplicatePolicy, Exporter, RenderOptions},
+};
 use clap::Parser;
 use clap::{crate_name, ArgAction, ValueEnum, ValueHint};
-use color_eyre::{eyre::Report, Result};
-use std::path::PathBuf;
+use color_eyre::{
+    eyre::{bail, Report},
+    Result,
+};
+use std::{collections::BTreeSet, fs, path::PathBuf};
 
 /// Define the SVG exporters.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -33,6 +42,78 @@ impl From<ExporterArg> for Exporter {
     }
 }
 
+/// Define the data formats.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum DataFormatArg {
+    Csv,
+    Json,
+    JsonLines,
+}
+
+// These 2 `From` Traits are implemented mainly to make sure that [`DataFormat`]
+// and [`DataFormatArg`] stay in sync.
+impl From<DataFormat> for DataFormatArg {
+    fn from(format: DataFormat) -> Self {
+        match format {
+            DataFormat::Csv => Self::Csv,
+            DataFormat::Json => Self::Json,
+            DataFormat::JsonLines => Self::JsonLines,
+        }
+    }
+}
+impl From<DataFormatArg> for DataFormat {
+    fn from(format_arg: DataFormatArg) -> Self {
+        match format_arg {
+            DataFormatArg::Csv => Self::Csv,
+            DataFormatArg::Json => Self::Json,
+            DataFormatArg::JsonLines => Self::JsonLines,
+        }
+    }
+}
+
+/// Define the policies applied to duplicate output names.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum DuplicatePolicyArg {
+    Suffix,
+    Error,
+}
+
+// These 2 `From` Traits are implemented mainly to make sure that
+// [`DuplicatePolicy`] and [`DuplicatePolicyArg`] stay in sync.
+impl From<DuplicatePolicy> for DuplicatePolicyArg {
+    fn from(policy: DuplicatePolicy) -> Self {
+        match policy {
+            DuplicatePolicy::Suffix => Self::Suffix,
+            DuplicatePolicy::Error => Self::Error,
+        }
+    }
+}
+impl From<DuplicatePolicyArg> for DuplicatePolicy {
+    fn from(policy_arg: DuplicatePolicyArg) -> Self {
+        match policy_arg {
+            DuplicatePolicyArg::Suffix => Self::Suffix,
+            DuplicatePolicyArg::Error => Self::Error,
+        }
+    }
+}
+
+/// Define the known data schemas a template can be linted against.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum SchemaArg {
+    ShortScoreCard,
+    ScoreCard24,
+}
+
+impl SchemaArg {
+    /// Return the field names of the schema.
+    pub fn fields(&self) -> &'static [&'static str] {
+        match self {
+            SchemaArg::ShortScoreCard => struct_fields::<ShortScoreCard>(),
+            SchemaArg::ScoreCard24 => struct_fields::<ScoreCard24>(),
+        }
+    }
+}
+
 // CLI options.
 #[derive(Parser, Debug)]
 #[clap(name = crate_name!(), author, about, version)]
@@ -48,6 +129,14 @@ pub struct Opts {
     // Ref: https://github.com/clap-rs/clap/issues/3066
     #[clap(long, action = ArgAction::Append, number_of_values = 1)]
     pub field: Option<Vec<String>>,
+    /// Specify the data file (CSV, JSON or JSON Lines), or `-` to read from stdin
+    ///
+    /// Defaults to the CSV file next to the template.
+    #[clap(short, long, value_hint = ValueHint::FilePath)]
+    pub data: Option<PathBuf>,
+    /// Specify the data format, instead of guessing it from the file extension
+    #[clap(long, value_enum)]
+    pub data_format: Option<DataFormatArg>,
     /// Specify the template
     #[clap(value_parser, value_hint = ValueHint::FilePath)]
     pub template: PathBuf,
@@ -57,13 +146,50 @@ pub struct Opts {
     /// Specify the separator
     #[clap(short, long, default_value = "-")]
     pub separator: String,
+    /// Specify what to do when several records produce the same output name
+    #[clap(long, value_enum, default_value = "suffix")]
+    pub on_duplicate: DuplicatePolicyArg,
     /// Export the rendered template as PDF
     #[clap(short, long, value_enum)]
     pub exporter: Option<ExporterArg>,
+    /// Specify the directory containing the translation catalogs (`<locale>.ftl`)
+    ///
+    /// The records are rendered in the default locale unless another one is
+    /// selected.
+    #[clap(long, value_hint = ValueHint::DirPath)]
+    pub catalog: Option<PathBuf>,
+    /// Render all the records with a specific locale, like `es-ES`
+    #[clap(long, conflicts_with = "locale_from_country", requires = "catalog")]
+    pub locale: Option<String>,
+    /// Select the locale of each record from its country
+    #[clap(long, requires = "catalog")]
+    pub locale_from_country: bool,
+    /// Specify the field containing the country of a record
+    #[clap(long, default_value = "co", value_name = "FIELD")]
+    pub country_field: String,
+    /// Fail if the template references a variable missing from a record
+    #[clap(long)]
+    pub strict: bool,
+    /// Check the variables referenced by the template instead of rendering it
+    ///
+    /// The variables are checked against the data header, or against the
+    /// schema if one is specified.
+    #[clap(long)]
+    pub lint: bool,
+    /// Specify the schema to lint the template against
+    #[clap(long, value_enum, requires = "lint")]
+    pub schema: Option<SchemaArg>,
+    /// Upload the rendered files to an S3 location, like `s3://bucket/prefix`
+    #[clap(long, value_name = "S3_URL")]
+    pub upload: Option<UploadTarget>,
+    /// List the files which would be uploaded, without uploading them
+    #[clap(long, requires = "upload")]
+    pub dry_run: bool,
 }
 
 // Perform a data-merge operation, and export SVGs to PDFs.
-fn main() -> Result<(), Report> {
+#[tokio::main]
+async fn main() -> Result<(), Report> {
     // Setup the application.
     color_eyre::install()?;
 
@@ -73,13 +199,93 @@ fn main() -> Result<(), Report> {
     // Convert the exporter.
     let exporter: Option<Exporter> = opts.exporter.map(|e| e.into());
 
-    let _ = render(
-        &opts.template,
-        &opts.output_dir,
+    // Select the data source.
+    let data_path = opts
+        .data
+        .unwrap_or_else(|| opts.template.with_extension("csv"));
+    let data = match (data_path.to_str(), opts.data_format) {
+        (Some("-"), Some(format)) => DataSource::Stdin(format.into()),
+        (Some("-"), None) => DataSource::Stdin(DataFormat::Csv),
+        (_, Some(DataFormatArg::Csv)) => DataSource::Csv(data_path),
+        (_, Some(DataFormatArg::Json)) => DataSource::Json(data_path),
+        (_, Some(DataFormatArg::JsonLines)) => DataSource::JsonLines(data_path),
+        (_, None) => DataSource::from_path(&data_path)?,
+    };
+
+    // Prepare the localization.
+    let selection = match (opts.locale, opts.locale_from_country) {
+        (Some(locale), _) => LocaleSelection::Fixed(locale),
+        (None, true) => LocaleSelection::FromCountry(opts.country_field),
+        (None, false) => LocaleSelection::Fixed(DEFAULT_LOCALE.to_string()),
+    };
+    let localization = match &opts.catalog {
+        Some(dir) => {
+            let catalog = Catalog::from_dir(dir, DEFAULT_LOCALE)?;
+            Some(Localization::new(catalog, selection))
+        }
+        None => None,
+    };
+
+    // Lint the template.
+    if opts.lint {
+        let fields: Vec<String> = match opts.schema {
+            Some(schema) => schema.fields().iter().map(|f| f.to_string()).collect(),
+            None => data
+                .records()?
+                .iter()
+                .flat_map(|r| r.keys().cloned())
+                .collect::<BTreeSet<String>>()
+                .into_iter()
+                .collect(),
+        };
+        let template = fs::read_to_string(&opts.template)?;
+        let report = lint(&template, &fields, localization.as_ref())?;
+        for variable in &report.referenced {
+            let status = if report.undefined.contains(variable) {
+                "undefined"
+            } else {
+                "ok"
+            };
+            println!("{variable}: {status}");
+        }
+        if !report.is_ok() {
+            bail!(
+                "{} undefined variable(s) in {}: {}",
+                report.undefined.len(),
+                opts.template.display(),
+                report
+                    .undefined
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+        }
+        return Ok(());
+    }
+
+    let options = RenderOptions {
         exporter,
-        opts.field,
-        Some(&opts.separator),
-    );
+        field_based_name: opts.field,
+        separator: Some(&opts.separator),
+        localization: localization.as_ref(),
+        strict: opts.strict,
+        duplicates: opts.on_duplicate.into(),
+    };
+    render(&opts.template, &data, &opts.output_dir, &options)?;
+
+    // Upload the rendered files.
+    if let Some(target) = &opts.upload {
+        let storage = target.storage().await;
+        let uploader = Uploader {
+            storage: &storage,
+            target,
+            dry_run: opts.dry_run,
+        };
+        for entry in uploader.upload_dir(&opts.output_dir).await? {
+            println!("{entry}");
+        }
+    }
 
     Ok(())
 }